@@ -0,0 +1,365 @@
+extern crate shape_predictor;
+extern crate image;
+
+use shape_predictor::{ShapePredictor, Rectangle, Vector2};
+use shape_predictor::shapes::centroid;
+use std::io::{self, BufRead, BufReader, Write};
+use std::fs::File;
+use std::cmp::Ordering;
+use std::process;
+
+const USAGE: &str = "Usage:
+    shape-predictor convert <input> <output>
+        Convert a model between the dlib format and the native format of this crate, which are the only
+        formats supported. Files ending in `.dat` are read and written in the dlib format, anything else
+        uses the native format.
+    shape-predictor predict <model> <boxes> [--format json|csv]
+        Find landmarks for each line of `boxes`, formatted as `image,x,y,width,height`.
+    shape-predictor inspect <model>
+        Print statistics about a model.
+    shape-predictor eval <model> <dataset>
+        Measure the accuracy of a model against each line of `dataset`, formatted as
+        `image,x,y,width,height,x0,y0,x1,y1,...`.";
+
+type CliResult = Result<(), String>;
+
+fn is_dlib(filename: &str) -> bool {
+    filename.ends_with(".dat")
+}
+
+fn load_model(filename: &str) -> Result<ShapePredictor, String> {
+    let result = if is_dlib(filename) {
+        ShapePredictor::read_from_dlib(filename)
+    } else {
+        ShapePredictor::read(filename)
+    };
+
+    result.map_err(|error| format!("Failed to read model '{}': {:?}", filename, error))
+}
+
+fn load_image(filename: &str) -> Result<image::GrayImage, String> {
+    image::open(filename)
+        .map(|image| image.to_luma())
+        .map_err(|error| format!("Failed to open image '{}': {}", filename, error))
+}
+
+/// A line from a boxes or dataset file.
+struct Entry {
+    image: String,
+    rect: Rectangle,
+    landmarks: Vec<Vector2>
+}
+
+fn read_entries(filename: &str) -> Result<Vec<Entry>, String> {
+    let file = File::open(filename).map_err(|error| format!("Failed to open '{}': {}", filename, error))?;
+    let mut entries = Vec::new();
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|error| format!("Failed to read '{}': {}", filename, error))?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split(',').map(str::trim);
+        let image = fields.next().unwrap().to_string();
+        let values = fields
+            .map(|field| field.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|error| format!("{}:{}: {}", filename, i + 1, error))?;
+
+        if values.len() < 4 || values.len() % 2 != 0 {
+            return Err(format!("{}:{}: expected a box followed by pairs of coordinates", filename, i + 1));
+        }
+
+        entries.push(Entry {
+            image,
+            rect: Rectangle::new(values[0], values[1], values[2], values[3]),
+            landmarks: values[4 ..].chunks(2).map(|pair| Vector2::new(pair[0], pair[1])).collect()
+        });
+    }
+
+    Ok(entries)
+}
+
+fn json_string(string: &str) -> String {
+    let mut escaped = String::from("\"");
+
+    for character in string.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            character if (character as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character)
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+fn convert(input: &str, output: &str) -> CliResult {
+    let model = load_model(input)?;
+
+    let result = if is_dlib(output) {
+        model.write_to_dlib(output)
+    } else {
+        model.write(output)
+    };
+
+    result.map_err(|error| format!("Failed to write model '{}': {:?}", output, error))
+}
+
+fn predict(model: &str, boxes: &str, json: bool) -> CliResult {
+    let model = load_model(model)?;
+    let entries = read_entries(boxes)?;
+    let stdout = io::stdout();
+
+    write_predictions(&mut stdout.lock(), &model, &entries, json)
+}
+
+fn write_predictions<W: Write>(output: &mut W, model: &ShapePredictor, entries: &[Entry], json: bool) -> CliResult {
+    let write_error = |error: io::Error| format!("Failed to write the landmarks: {}", error);
+    let mut image: Option<(String, image::GrayImage)> = None;
+
+    if json {
+        writeln!(output, "[").map_err(write_error)?;
+    } else {
+        writeln!(output, "image,x,y,width,height,part,landmark_x,landmark_y").map_err(write_error)?;
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        // Boxes for the same image are usually next to each other, so only reload when it changes
+        let reload = match &image {
+            Some((name, _)) => name != &entry.image,
+            None => true
+        };
+        if reload {
+            image = Some((entry.image.clone(), load_image(&entry.image)?));
+        }
+
+        let landmarks = model.run(&image.as_ref().unwrap().1, &entry.rect);
+        let rect = &entry.rect;
+
+        if json {
+            let points: Vec<String> = landmarks.iter().map(|point| format!("[{}, {}]", point.x, point.y)).collect();
+            writeln!(
+                output, "  {{\"image\": {}, \"box\": [{}, {}, {}, {}], \"landmarks\": [{}]}}{}",
+                json_string(&entry.image), rect.x, rect.y, rect.width, rect.height,
+                points.join(", "), if i + 1 < entries.len() { "," } else { "" }
+            ).map_err(write_error)?;
+        } else {
+            for (part, point) in landmarks.iter().enumerate() {
+                writeln!(
+                    output, "{},{},{},{},{},{},{},{}",
+                    entry.image, rect.x, rect.y, rect.width, rect.height, part, point.x, point.y
+                ).map_err(write_error)?;
+            }
+        }
+    }
+
+    if json {
+        writeln!(output, "]").map_err(write_error)?;
+    }
+
+    Ok(())
+}
+
+fn inspect(model: &str) -> CliResult {
+    let model = load_model(model)?;
+
+    println!("Parts: {}", model.num_parts());
+    println!("Cascade depth: {}", model.cascade_depth());
+    println!("Trees per cascade: {:?}", model.trees_per_cascade());
+    println!("Tree depth: {}", model.tree_depth());
+    println!("Feature pool sizes: {:?}", model.feature_pool_sizes());
+    println!("Memory footprint: {} bytes", model.memory_footprint());
+
+    println!("Mean shape:");
+    for (i, (point, usage)) in model.mean_shape().iter().zip(model.anchor_usage()).enumerate() {
+        println!("    {}: ({}, {}), used as an anchor {} times", i, point.x, point.y, usage);
+    }
+
+    Ok(())
+}
+
+/// The distance that errors are measured relative to: the distance between the centers of the eyes for
+/// 68 point models (as in dlib's `interocular_distance`), otherwise the diagonal of the box.
+fn normaliser(entry: &Entry) -> f32 {
+    if entry.landmarks.len() == 68 {
+        (centroid(&entry.landmarks[36 .. 42]) - centroid(&entry.landmarks[42 .. 48])).norm()
+    } else {
+        (entry.rect.br_corner() - entry.rect.tl_corner()).norm()
+    }
+}
+
+/// The normalised mean error of each face in a dataset.
+struct Evaluation {
+    /// The errors, sorted from the smallest.
+    errors: Vec<f32>,
+    /// The number of faces whose error couldn't be normalised.
+    skipped: usize
+}
+
+fn evaluate(model: &ShapePredictor, entries: &[Entry]) -> Result<Evaluation, String> {
+    let mut errors = Vec::with_capacity(entries.len());
+    let mut skipped = 0;
+
+    for entry in entries {
+        if entry.landmarks.len() != model.num_parts() {
+            return Err(format!(
+                "'{}' has {} landmarks but the model has {} parts",
+                entry.image, entry.landmarks.len(), model.num_parts()
+            ));
+        }
+
+        let image = load_image(&entry.image)?;
+        let landmarks = model.run(&image, &entry.rect);
+
+        let error = landmarks.iter().zip(&entry.landmarks)
+            .map(|(predicted, expected)| (predicted - expected).norm())
+            .sum::<f32>() / landmarks.len() as f32;
+
+        let error = error / normaliser(entry);
+
+        // Coincident eyes or an empty box leave nothing to measure the error against
+        if !error.is_finite() {
+            eprintln!("Skipping a face in '{}' whose error can't be normalised", entry.image);
+            skipped += 1;
+            continue;
+        }
+
+        errors.push(error);
+    }
+
+    errors.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    Ok(Evaluation {
+        errors, skipped
+    })
+}
+
+fn eval(model: &str, dataset: &str) -> CliResult {
+    let model = load_model(model)?;
+    let entries = read_entries(dataset)?;
+    let Evaluation { errors, skipped } = evaluate(&model, &entries)?;
+
+    if errors.is_empty() {
+        return Err(format!("'{}' contains no usable annotations", dataset));
+    }
+
+    let mean = errors.iter().sum::<f32>() / errors.len() as f32;
+
+    println!("Faces: {}", errors.len());
+    if skipped > 0 {
+        println!("Skipped faces: {}", skipped);
+    }
+    println!("Mean normalised error: {}", mean);
+    println!("Median normalised error: {}", errors[errors.len() / 2]);
+    println!("Max normalised error: {}", errors[errors.len() - 1]);
+
+    Ok(())
+}
+
+fn run(args: &[String]) -> CliResult {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["convert", input, output] => convert(input, output),
+        ["predict", model, boxes] => predict(model, boxes, true),
+        ["predict", model, boxes, "--format", "json"] => predict(model, boxes, true),
+        ["predict", model, boxes, "--format", "csv"] => predict(model, boxes, false),
+        ["inspect", model] => inspect(model),
+        ["eval", model, dataset] => eval(model, dataset),
+        _ => Err(USAGE.to_string())
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if let Err(error) = run(&args) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // A 68 point model without any cascades, so it always predicts its mean shape. Part `i` of the mean
+    // shape is at `((i % 17) / 16, (i / 17) / 4)`.
+    const MODEL: &str = "test_files/mean_shape_68.dat";
+
+    fn write_image(name: &str) -> String {
+        let path = env::temp_dir().join(name);
+        image::GrayImage::new(32, 32).save(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn evaluate_against_eye_centers() {
+        let model = load_model(MODEL).unwrap();
+        let image = write_image("shape-predictor-evaluate.png");
+        let rect = Rectangle::new(0.0, 0.0, 160.0, 160.0);
+        let predicted = model.run(&load_image(&image).unwrap(), &rect);
+
+        // Every landmark is 5 pixels from the prediction
+        let shifted = Entry {
+            image: image.clone(),
+            rect,
+            landmarks: predicted.iter().map(|point| point + Vector2::new(3.0, 4.0)).collect()
+        };
+
+        // The outer eye corners are 90 pixels apart, but the centers of the eyes are 60 pixels apart
+        assert!(((shifted.landmarks[36] - shifted.landmarks[45]).norm() - 90.0).abs() < 1e-3);
+        assert!((normaliser(&shifted) - 60.0).abs() < 1e-3);
+
+        // With every landmark in the same place there is nothing to normalise the error by
+        let collapsed = Entry {
+            image,
+            rect,
+            landmarks: vec![Vector2::zeros(); 68]
+        };
+
+        let evaluation = evaluate(&model, &[shifted, collapsed]).unwrap();
+        assert_eq!(evaluation.skipped, 1);
+        assert_eq!(evaluation.errors.len(), 1);
+        assert!((evaluation.errors[0] - 5.0 / 60.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn predictions() {
+        let model = load_model(MODEL).unwrap();
+        let image = write_image("shape-predictor-predictions.png");
+        let entries = vec![Entry {
+            image: image.clone(),
+            rect: Rectangle::new(0.0, 0.0, 160.0, 160.0),
+            landmarks: Vec::new()
+        }];
+
+        let mut json = Vec::new();
+        write_predictions(&mut json, &model, &entries, true).unwrap();
+        let json = String::from_utf8(json).unwrap();
+
+        assert!(json.starts_with(&format!("[\n  {{\"image\": {}, \"box\": [0, 0, 160, 160], \"landmarks\": [[", json_string(&image))));
+        assert!(json.ends_with("]]}\n]\n"));
+        assert_eq!(json.matches("], [").count(), 67);
+
+        let mut csv = Vec::new();
+        write_predictions(&mut csv, &model, &entries, false).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 69);
+        assert_eq!(lines[0], "image,x,y,width,height,part,landmark_x,landmark_y");
+        assert!(lines[2].starts_with(&format!("{},0,0,160,160,1,", image)));
+
+        // The second part of the mean shape is a sixteenth of the way along the top of the box
+        let point: Vec<f32> = lines[2].rsplit(',').take(2).map(|value| value.parse().unwrap()).collect();
+        assert!((point[1] - 10.0).abs() < 1e-3 && point[0].abs() < 1e-3);
+    }
+}
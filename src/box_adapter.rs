@@ -0,0 +1,163 @@
+use std::io::{BufReader, BufWriter};
+use std::fs::File;
+use bincode;
+use {Rectangle, Error};
+
+/// A correction from the boxes of one face detector to the boxes that a shape predictor expects,
+/// e.g. from rustface's boxes to dlib's HOG detector boxes.
+///
+/// Each of x, y, width and height is corrected with a scale and an offset. The center of the box is shifted
+/// by `x_offset * width + x_bias` horizontally and `y_offset * height + y_bias` vertically, and the new size is
+/// `width_scale * width + width_bias` by `height_scale * height + height_bias`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoxAdapter {
+    /// The shift of the center of the box, as a fraction of its width.
+    pub x_offset: f32,
+    /// The shift of the center of the box, as a fraction of its height.
+    pub y_offset: f32,
+    pub width_scale: f32,
+    pub height_scale: f32,
+    /// The shift of the center of the box that doesn't depend on its size, in pixels.
+    pub x_bias: f32,
+    /// The shift of the center of the box that doesn't depend on its size, in pixels.
+    pub y_bias: f32,
+    /// The change in width that doesn't depend on the size of the box, in pixels.
+    pub width_bias: f32,
+    /// The change in height that doesn't depend on the size of the box, in pixels.
+    pub height_bias: f32
+}
+
+impl Default for BoxAdapter {
+    fn default() -> Self {
+        Self {
+            x_offset: 0.0,
+            y_offset: 0.0,
+            width_scale: 1.0,
+            height_scale: 1.0,
+            x_bias: 0.0,
+            y_bias: 0.0,
+            width_bias: 0.0,
+            height_bias: 0.0
+        }
+    }
+}
+
+// A least squares fit of `target = scale * size + bias` to pairs of sizes and targets. When the sizes are
+// too similar to tell the scale and bias apart, the bias is left at zero.
+fn linear_fit<I: Iterator<Item = (f32, f32)> + Clone>(samples: I) -> (f32, f32) {
+    let count = samples.clone().count() as f32;
+    let mean_size = samples.clone().map(|(size, _)| size).sum::<f32>() / count;
+    let mean_target = samples.clone().map(|(_, target)| target).sum::<f32>() / count;
+    let variance = samples.clone().map(|(size, _)| (size - mean_size).powi(2)).sum::<f32>();
+
+    if variance > 1e-6 * count * mean_size.powi(2) {
+        let covariance = samples.map(|(size, target)| (size - mean_size) * (target - mean_target)).sum::<f32>();
+        let scale = covariance / variance;
+        (scale, mean_target - scale * mean_size)
+    } else {
+        let scale = samples.clone().map(|(size, target)| size * target).sum::<f32>() /
+            samples.map(|(size, _)| size.powi(2)).sum::<f32>();
+        (scale, 0.0)
+    }
+}
+
+impl BoxAdapter {
+    /// Learn a correction from pairs of boxes, each being a box from the detector and the box that the shape
+    /// predictor expects for the same face. Each scale and bias is a least squares fit against the size
+    /// of the detected boxes, so the biases need boxes of a few different sizes.
+    pub fn fit(pairs: &[(Rectangle, Rectangle)]) -> Result<Self, Error> {
+        let pairs: Vec<&(Rectangle, Rectangle)> = pairs.iter()
+            .filter(|(detected, _)| detected.width > 0.0 && detected.height > 0.0)
+            .collect();
+
+        if pairs.is_empty() {
+            return Err(Error::NotEnoughSamples);
+        }
+
+        let (x_offset, x_bias) = linear_fit(pairs.iter()
+            .map(|(detected, expected)| (detected.width, expected.center().x - detected.center().x)));
+
+        let (y_offset, y_bias) = linear_fit(pairs.iter()
+            .map(|(detected, expected)| (detected.height, expected.center().y - detected.center().y)));
+
+        let (width_scale, width_bias) = linear_fit(pairs.iter()
+            .map(|(detected, expected)| (detected.width, expected.width)));
+
+        let (height_scale, height_bias) = linear_fit(pairs.iter()
+            .map(|(detected, expected)| (detected.height, expected.height)));
+
+        Ok(Self {
+            x_offset, y_offset, width_scale, height_scale,
+            x_bias, y_bias, width_bias, height_bias
+        })
+    }
+
+    /// Correct a box from the detector.
+    pub fn apply(&self, rect: &Rectangle) -> Rectangle {
+        let mut center = rect.center();
+        center.x += self.x_offset * rect.width + self.x_bias;
+        center.y += self.y_offset * rect.height + self.y_bias;
+
+        Rectangle::from_center(
+            center,
+            rect.width * self.width_scale + self.width_bias,
+            rect.height * self.height_scale + self.height_bias
+        )
+    }
+
+    /// Serialize the box adapter to a file.
+    pub fn write(&self, filename: &str) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        bincode::serialize_into(&mut writer, self, bincode::Infinite)?;
+        Ok(())
+    }
+
+    /// Deserialize the box adapter from a file.
+    pub fn read(filename: &str) -> Result<Self, Error> {
+        let mut reader = BufReader::new(File::open(filename)?);
+        Ok(bincode::deserialize_from(&mut reader, bincode::Infinite)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use box_adapter::*;
+
+    #[test]
+    fn fit() {
+        let truth = BoxAdapter {
+            x_offset: 0.05,
+            y_offset: 0.1,
+            width_scale: 1.2,
+            height_scale: 1.25,
+            x_bias: 2.0,
+            y_bias: -3.0,
+            width_bias: 4.0,
+            height_bias: -5.0
+        };
+
+        let pairs: Vec<(Rectangle, Rectangle)> = [
+            Rectangle::new(10.0, 20.0, 40.0, 40.0),
+            Rectangle::new(100.0, 50.0, 80.0, 88.0),
+            Rectangle::new(0.0, 0.0, 120.0, 100.0)
+        ].iter().map(|rect| (*rect, truth.apply(rect))).collect();
+
+        let adapter = BoxAdapter::fit(&pairs).unwrap();
+
+        assert!((adapter.x_offset - truth.x_offset).abs() < 1e-5);
+        assert!((adapter.y_offset - truth.y_offset).abs() < 1e-5);
+        assert!((adapter.width_scale - truth.width_scale).abs() < 1e-5);
+        assert!((adapter.height_scale - truth.height_scale).abs() < 1e-5);
+        assert!((adapter.x_bias - truth.x_bias).abs() < 1e-3);
+        assert!((adapter.y_bias - truth.y_bias).abs() < 1e-3);
+        assert!((adapter.width_bias - truth.width_bias).abs() < 1e-3);
+        assert!((adapter.height_bias - truth.height_bias).abs() < 1e-3);
+
+        // A single box can't separate the scales from the biases, so only the scales are fitted
+        let single = BoxAdapter::fit(&pairs[.. 1]).unwrap();
+        assert_eq!((single.x_bias, single.width_bias), (0.0, 0.0));
+        assert_eq!(single.apply(&pairs[0].0), pairs[0].1);
+
+        assert!(BoxAdapter::fit(&[]).is_err());
+    }
+}
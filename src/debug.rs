@@ -0,0 +1,32 @@
+use {ShapePredictor, PointTransformationAffine, IntensitySource, Rectangle, Vector2};
+use stages::Cascade;
+
+/// The pixels sampled by one cascade of the shape predictor.
+#[derive(Debug, Clone)]
+pub struct CascadeSamples {
+    /// The position of each sampled pixel in the image: the anchor landmark plus the transformed delta.
+    pub positions: Vec<Vector2>,
+    /// The intensity of each sampled pixel. Pixels outside of the image have an intensity of 0.
+    pub intensities: Vec<f32>,
+    /// The landmarks after the cascade has been applied, in image coordinates.
+    pub shape: Vec<Vector2>
+}
+
+impl ShapePredictor {
+    /// Run the shape predictor like `run`, but record the pixels sampled by each cascade.
+    /// This is slower than `run` and is meant for finding out why a model fails on an image.
+    pub fn sample_cascades<I: IntensitySource>(&self, image: &I, region: &Rectangle) -> Vec<CascadeSamples> {
+        let mut cascade = Cascade::new(self, PointTransformationAffine::unnormalising(region));
+        let mut cascades = Vec::with_capacity(self.forests.len());
+
+        while cascade.step(image) {
+            cascades.push(CascadeSamples {
+                positions: cascade.positions.clone(),
+                intensities: cascade.intensities.clone(),
+                shape: cascade.image_shape()
+            });
+        }
+
+        cascades
+    }
+}
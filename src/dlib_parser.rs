@@ -1,6 +1,6 @@
 use nom::*;
 
-use nalgebra::{Dim, MatrixVec};
+use nalgebra::{Dim, VecStorage};
 use nalgebra::core::dimension::Dynamic;
 use {SplitFeature, RegressionTree, Matrix, ShapePredictor, Error, Vector2};
 
@@ -68,7 +68,7 @@ named!(matrix<Matrix>, do_parse!(
     dimensions: matrix_dimensions >>
     values: many_m_n!(dimensions.0 * dimensions.1, dimensions.0 * dimensions.1, dlib_float) >>
     ({
-        let data = MatrixVec::new(Dynamic::from_usize(dimensions.0), Dynamic::from_usize(dimensions.1), values);
+        let data = VecStorage::new(Dynamic::from_usize(dimensions.0), Dynamic::from_usize(dimensions.1), values);
         Matrix::from_data(data)
     })
 ));
@@ -125,6 +125,105 @@ named!(shape_predictor<ShapePredictor>, do_parse!(
     })
 ));
 
+// https://github.com/davisking/dlib/blob/master/dlib/serialize.h#L134
+// https://github.com/davisking/dlib/blob/master/dlib/float_details.h#L143
+named!(dlib_double<f64>, do_parse!(
+    mantissa: dlib_int >>
+    exponent: dlib_int >>
+    ((mantissa as f64) * 2.0_f64.powf(exponent as f64))
+));
+
+// https://github.com/davisking/dlib/blob/master/dlib/matrix/matrix.h#L1888
+named!(double_column<Vec<f64>>, do_parse!(
+    dimensions: matrix_dimensions >>
+    values: many_m_n!(dimensions.0 * dimensions.1, dimensions.0 * dimensions.1, dlib_double) >>
+    (values)
+));
+
+// https://github.com/davisking/dlib/blob/master/dlib/array2d/array2d_kernel.h
+named!(float_plane<()>, do_parse!(
+    dimensions: matrix_dimensions >>
+    many_m_n!(dimensions.0 * dimensions.1, dimensions.0 * dimensions.1, dlib_float) >>
+    (())
+));
+
+// https://github.com/davisking/dlib/blob/master/dlib/array/array_kernel.h
+named!(float_planes<()>, do_parse!(
+    _max_size: dlib_int >>
+    len: dlib_int >>
+    many_m_n!(len as usize, len as usize, float_plane) >>
+    (())
+));
+
+// A cached feature pyramid. It is empty in saved detectors, but skipped over in case it isn't.
+named!(fhog_pyramid<()>, do_parse!(
+    _max_size: dlib_int >>
+    len: dlib_int >>
+    many_m_n!(len as usize, len as usize, float_planes) >>
+    (())
+));
+
+/// The settings of a `scan_fhog_pyramid` with the default fHOG feature extractor.
+#[derive(Debug, Clone)]
+pub struct FhogScanner {
+    pub cell_size: i64,
+    pub padding: i64,
+    pub window_width: i64,
+    pub window_height: i64,
+    pub max_pyramid_levels: i64,
+    pub min_pyramid_layer_width: i64,
+    pub min_pyramid_layer_height: i64
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/image_processing/scan_fhog_pyramid.h
+named!(scan_fhog_pyramid<FhogScanner>, do_parse!(
+    verify!(dlib_int, |version: i64| version == 1) >>
+    fhog_pyramid >>
+    cell_size: dlib_int >>
+    padding: dlib_int >>
+    window_width: dlib_int >>
+    window_height: dlib_int >>
+    max_pyramid_levels: dlib_int >>
+    min_pyramid_layer_width: dlib_int >>
+    min_pyramid_layer_height: dlib_int >>
+    _nuclear_norm_regularization_strength: dlib_double >>
+    (FhogScanner {
+        cell_size, padding, window_width, window_height, max_pyramid_levels,
+        min_pyramid_layer_width, min_pyramid_layer_height
+    })
+));
+
+/// A dlib `object_detector<scan_fhog_pyramid<pyramid_down<6>>>`, such as `get_frontal_face_detector()`.
+#[derive(Debug, Clone)]
+pub struct ObjectDetector {
+    pub scanner: FhogScanner,
+    pub iou_thresh: f64,
+    pub percent_covered_thresh: f64,
+    pub weights: Vec<Vec<f64>>
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/image_processing/object_detector.h
+named!(object_detector<ObjectDetector>, do_parse!(
+    verify!(dlib_int, |version: i64| version == 2) >>
+    scanner: scan_fhog_pyramid >>
+    // https://github.com/davisking/dlib/blob/master/dlib/image_processing/box_overlap_testing.h
+    iou_thresh: dlib_double >>
+    percent_covered_thresh: dlib_double >>
+    len: dlib_int >>
+    weights: many_m_n!(len as usize, len as usize, double_column) >>
+    (ObjectDetector {
+        scanner, iou_thresh, percent_covered_thresh, weights
+    })
+));
+
+pub fn parse_object_detector(slice: &[u8]) -> Result<ObjectDetector, Error> {
+    match object_detector(slice) {
+        IResult::Done(_, detector) => Ok(detector),
+        IResult::Incomplete(needed) => Err(Error::ReachedEof(needed)),
+        IResult::Error(error) => Err(Error::ParsingError(error.into_error_kind()))
+    }
+}
+
 pub fn parse_shape_predictor(slice: &[u8]) -> Result<ShapePredictor, Error> {
     match shape_predictor(slice) {
         IResult::Done(_, predictor) => Ok(predictor),
@@ -141,7 +240,7 @@ mod tests {
 
     fn read_bytes(filename: &str) -> Vec<u8> {
         let mut vec = Vec::new();
-        File::open(&format!("test_files/{}", filename)).unwrap().read_to_end(&mut vec).unwrap();
+        File::open(format!("test_files/{}", filename)).unwrap().read_to_end(&mut vec).unwrap();
         println!("{:?}", vec);
         vec
     }
@@ -0,0 +1,177 @@
+use std::io::{self, Write};
+use {SplitFeature, RegressionTree, Matrix, ShapePredictor, Vector2};
+
+// https://github.com/davisking/dlib/blob/master/dlib/serialize.h#L230
+fn dlib_int<W: Write>(writer: &mut W, int: i64) -> io::Result<()> {
+    let mut buf = [0_u8; 9];
+    let mut magnitude = if int < 0 {
+        (int as u64).wrapping_neg()
+    } else {
+        int as u64
+    };
+
+    let mut size = 0;
+
+    while magnitude != 0 {
+        size += 1;
+        buf[size] = (magnitude & 0xFF) as u8;
+        magnitude >>= 8;
+    }
+
+    buf[0] = size as u8;
+
+    if int < 0 {
+        buf[0] |= 0x80;
+    }
+
+    writer.write_all(&buf[..= size])
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/float_details.h#L143
+fn dlib_float<W: Write>(writer: &mut W, float: f32) -> io::Result<()> {
+    debug_assert!(float.is_finite());
+
+    let bits = float.to_bits();
+    let biased_exponent = ((bits >> 23) & 0xFF) as i64;
+    let fraction = i64::from(bits & 0x7F_FFFF);
+
+    // Split the float into `mantissa * 2^exponent` with an integer mantissa
+    let (mut mantissa, mut exponent) = if biased_exponent == 0 {
+        // Subnormal numbers and zero
+        (fraction, -149)
+    } else {
+        (fraction | 0x80_0000, biased_exponent - 150)
+    };
+
+    if mantissa == 0 {
+        exponent = 0;
+    } else {
+        while mantissa % 2 == 0 {
+            mantissa /= 2;
+            exponent += 1;
+        }
+    }
+
+    if bits >> 31 == 1 {
+        mantissa = -mantissa;
+    }
+
+    dlib_int(writer, mantissa)?;
+    dlib_int(writer, exponent)
+}
+
+fn dlib_len<W: Write>(writer: &mut W, len: usize) -> io::Result<()> {
+    dlib_int(writer, len as i64)
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/geometry/vector.h#L1133
+fn vector2<W: Write>(writer: &mut W, vector: &Vector2) -> io::Result<()> {
+    dlib_float(writer, vector.x)?;
+    dlib_float(writer, vector.y)
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/image_processing/shape_predictor.h#L34
+fn split_feature<W: Write>(writer: &mut W, split: &SplitFeature) -> io::Result<()> {
+    dlib_int(writer, split.idx1 as i64)?;
+    dlib_int(writer, split.idx2 as i64)?;
+    dlib_float(writer, split.thresh)
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/matrix/matrix.h#L1888
+fn matrix<W: Write>(writer: &mut W, matrix: &Matrix) -> io::Result<()> {
+    // dlib writes negative dimensions to mark the current format
+    dlib_int(writer, -(matrix.nrows() as i64))?;
+    dlib_int(writer, -(matrix.ncols() as i64))?;
+
+    for value in matrix.iter() {
+        dlib_float(writer, *value)?;
+    }
+
+    Ok(())
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/image_processing/shape_predictor.h#L96
+fn regression_tree<W: Write>(writer: &mut W, tree: &RegressionTree) -> io::Result<()> {
+    dlib_len(writer, tree.splits.len())?;
+    for split in &tree.splits {
+        split_feature(writer, split)?;
+    }
+
+    dlib_len(writer, tree.leaf_values.len())?;
+    for leaf in &tree.leaf_values {
+        matrix(writer, leaf)?;
+    }
+
+    Ok(())
+}
+
+// https://github.com/davisking/dlib/blob/master/dlib/image_processing/shape_predictor.h#L408
+pub fn write_shape_predictor<W: Write>(writer: &mut W, predictor: &ShapePredictor) -> io::Result<()> {
+    // Version
+    dlib_int(writer, 1)?;
+    matrix(writer, &predictor.initial_shape)?;
+
+    dlib_len(writer, predictor.forests.len())?;
+    for forest in &predictor.forests {
+        dlib_len(writer, forest.len())?;
+        for tree in forest {
+            regression_tree(writer, tree)?;
+        }
+    }
+
+    dlib_len(writer, predictor.anchor_idx.len())?;
+    for anchors in &predictor.anchor_idx {
+        dlib_len(writer, anchors.len())?;
+        for anchor in anchors {
+            dlib_int(writer, *anchor as i64)?;
+        }
+    }
+
+    dlib_len(writer, predictor.deltas.len())?;
+    for deltas in &predictor.deltas {
+        dlib_len(writer, deltas.len())?;
+        for delta in deltas {
+            vector2(writer, delta)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dlib_writer::*;
+    use dlib_parser::parse_shape_predictor;
+    use test_predictors::column;
+
+    #[test]
+    fn int() {
+        let mut bytes = Vec::new();
+        dlib_int(&mut bytes, 0).unwrap();
+        dlib_int(&mut bytes, -1000).unwrap();
+        assert_eq!(bytes, [0x00, 0x82, 0xe8, 0x03]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let predictor = ShapePredictor {
+            initial_shape: column(vec![0.25, 0.5, -0.75, 1.0e-3]),
+            forests: vec![vec![RegressionTree {
+                splits: vec![SplitFeature { idx1: 0, idx2: 1, thresh: -12.5 }],
+                leaf_values: vec![column(vec![0.1, 0.2, 0.3, 0.4]), column(vec![-0.1, -0.2, -0.3, -0.4])]
+            }]],
+            deltas: vec![vec![Vector2::new(0.125, -3.0), Vector2::new(1.0e-30, 0.0)]],
+            anchor_idx: vec![vec![0, 1]]
+        };
+
+        let mut bytes = Vec::new();
+        write_shape_predictor(&mut bytes, &predictor).unwrap();
+        let parsed = parse_shape_predictor(&bytes).unwrap();
+
+        assert_eq!(parsed.initial_shape, predictor.initial_shape);
+        assert_eq!(parsed.forests[0][0].leaf_values, predictor.forests[0][0].leaf_values);
+        assert_eq!(parsed.forests[0][0].splits[0].thresh, -12.5);
+        assert_eq!(parsed.deltas, predictor.deltas);
+        assert_eq!(parsed.anchor_idx, predictor.anchor_idx);
+    }
+}
@@ -0,0 +1,60 @@
+use {ShapePredictor, PointTransformationAffine, IntensitySource, Rectangle, Vector2, Vector2d, Error};
+use stages::Cascade;
+
+fn to_f64(point: Vector2) -> Vector2d {
+    point.map(f64::from)
+}
+
+fn unnormalising(region: &Rectangle) -> PointTransformationAffine<f64> {
+    PointTransformationAffine::from_unit_square(to_f64(region.tl_corner()), to_f64(region.tr_corner()), to_f64(region.br_corner()))
+}
+
+impl ShapePredictor {
+    /// Run the shape predictor like `run`, but with the transforms and the shape in double precision.
+    /// The model itself stays in single precision, so any model can be used. This is slower than `run`,
+    /// but avoids rounding errors for very large images and for shapes that are tracked over many frames.
+    pub fn run_f64<I: IntensitySource>(&self, image: &I, region: &Rectangle) -> Vec<Vector2d> {
+        self.run_with_transform_f64(image, &unnormalising(region))
+    }
+
+    /// Run the shape predictor in double precision with a transform that maps the unit square onto the region in the image.
+    pub fn run_with_transform_f64<I: IntensitySource>(&self, image: &I, tform_to_img: &PointTransformationAffine<f64>) -> Vec<Vector2d> {
+        Cascade::new(self, tform_to_img.clone()).run(image)
+    }
+
+    /// Run the shape predictor in double precision, starting from a set of landmarks in image coordinates,
+    /// such as the landmarks from a previous frame.
+    pub fn run_from_shape_f64<I: IntensitySource>(&self, image: &I, region: &Rectangle, shape: &[Vector2d]) -> Result<Vec<Vector2d>, Error> {
+        if shape.len() != self.num_parts() {
+            return Err(Error::MismatchedParts { expected: self.num_parts(), found: shape.len() });
+        }
+
+        let tform_to_img = unnormalising(region);
+        let tform_from_img = PointTransformationAffine::to_unit_square(
+            to_f64(region.tl_corner()), to_f64(region.tr_corner()), to_f64(region.br_corner())
+        );
+
+        Ok(Cascade::from_shape(self, tform_to_img, tform_from_img.apply(shape)).run(image))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Rectangle;
+    use test_predictors::two_cascade_predictor;
+    use image::{GrayImage, Luma};
+
+    #[test]
+    fn matches_single_precision() {
+        let predictor = two_cascade_predictor();
+        let image = GrayImage::from_fn(64, 64, |x, _| Luma([(x * 4) as u8]));
+        let region = Rectangle::new(8.0, 8.0, 48.0, 48.0);
+
+        let single = predictor.run(&image, &region);
+        let double = predictor.run_f64(&image, &region);
+
+        for (a, b) in single.iter().zip(&double) {
+            assert!((f64::from(a.x) - b.x).abs() < 1e-4 && (f64::from(a.y) - b.y).abs() < 1e-4);
+        }
+    }
+}
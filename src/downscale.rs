@@ -0,0 +1,156 @@
+use {ShapePredictor, IntensitySource, Rectangle, Vector2};
+
+// A region of an image, downscaled with a box filter. Each pixel is averaged from the image when it's
+// sampled, so the cost depends on the number of pixels the cascade samples rather than on the size of the region.
+struct Downscaled<'a, I: 'a> {
+    image: &'a I,
+    area: Rectangle,
+    scale: f32,
+    width: u32,
+    height: u32
+}
+
+// The range of pixels of the image that a pixel of the downscaled region covers, clipped to the image.
+fn source_range(start: f32, idx: u32, scale: f32, size: u32) -> (u32, u32) {
+    let from = (start + idx as f32 / scale).floor().clamp(0.0, size as f32);
+    let to = (start + (idx + 1) as f32 / scale).floor().clamp(0.0, size as f32);
+
+    (from as u32, to as u32)
+}
+
+impl<'a, I: IntensitySource> Downscaled<'a, I> {
+    fn new(image: &'a I, area: &Rectangle, scale: f32) -> Self {
+        Self {
+            image, scale,
+            area: *area,
+            width: (area.width * scale).ceil().max(1.0) as u32,
+            height: (area.height * scale).ceil().max(1.0) as u32
+        }
+    }
+}
+
+impl<'a, I: IntensitySource> IntensitySource for Downscaled<'a, I> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn intensity(&self, x: u32, y: u32) -> f32 {
+        let (x0, x1) = source_range(self.area.x, x, self.scale, self.image.width());
+        let (y0, y1) = source_range(self.area.y, y, self.scale, self.image.height());
+        let count = (x1 - x0) * (y1 - y0);
+
+        // Pixels outside of the image are treated as black, like in `ShapePredictor::run`
+        if count == 0 {
+            return 0.0;
+        }
+
+        let mut sum = 0.0;
+        for source_y in y0 .. y1 {
+            for source_x in x0 .. x1 {
+                sum += self.image.intensity(source_x, source_y);
+            }
+        }
+
+        sum / count as f32
+    }
+}
+
+impl ShapePredictor {
+    /// Run the shape predictor on the area around the region downscaled so that the longest side of the region
+    /// is `target_size` pixels, and map the landmarks back to the image. Each sampled pixel is the average of the
+    /// image pixels it covers, rather than a single pixel of a large face, which matches the resolution that the
+    /// model was trained at: dlib's face models were trained on faces roughly 100 to 300 pixels wide. Only the
+    /// sampled pixels are averaged, so nothing is copied and the rest of the face is never read. Regions that
+    /// are already smaller than `target_size` are run as they are.
+    pub fn run_downscaled<I: IntensitySource>(&self, image: &I, region: &Rectangle, target_size: f32) -> Vec<Vector2> {
+        let size = region.width.max(region.height);
+        let scale = target_size / size;
+
+        if scale >= 1.0 || !scale.is_finite() {
+            return self.run(image, region);
+        }
+
+        // The cascade samples pixels outside of the region, so keep some of the image around it
+        let area = region.pad(size / 2.0);
+        let downscaled = Downscaled::new(image, &area, scale);
+        let downscaled_region = Rectangle::new((region.x - area.x) * scale, (region.y - area.y) * scale, region.width * scale, region.height * scale);
+
+        self.run(&downscaled, &downscaled_region).into_iter()
+            .map(|point| area.tl_corner() + point / scale)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use downscale::*;
+    use IntensitySource;
+    use test_predictors::{mean_shape_predictor, two_cascade_predictor};
+    use image::{GrayImage, Luma};
+    use std::cell::Cell;
+
+    // Counts the pixels that are read from an image.
+    struct Counting<'a> {
+        image: &'a GrayImage,
+        reads: Cell<usize>
+    }
+
+    impl<'a> IntensitySource for Counting<'a> {
+        fn width(&self) -> u32 {
+            self.image.width()
+        }
+
+        fn height(&self) -> u32 {
+            self.image.height()
+        }
+
+        fn intensity(&self, x: u32, y: u32) -> f32 {
+            self.reads.set(self.reads.get() + 1);
+            self.image.intensity(x, y)
+        }
+    }
+
+    #[test]
+    fn box_filter() {
+        let image = GrayImage::from_fn(4, 4, |x, y| Luma([(x * 10 + y * 40) as u8]));
+        let downscaled = Downscaled::new(&image, &Rectangle::new(0.0, 0.0, 4.0, 4.0), 0.5);
+
+        assert_eq!((downscaled.width, downscaled.height), (2, 2));
+        assert_eq!(downscaled.intensity(0, 0), 25.0);
+        assert_eq!(downscaled.intensity(1, 0), 45.0);
+        assert_eq!(downscaled.intensity(0, 1), 105.0);
+        assert_eq!(downscaled.intensity(1, 1), 125.0);
+    }
+
+    #[test]
+    fn reads_only_sampled_pixels() {
+        let predictor = two_cascade_predictor();
+        let image = GrayImage::from_fn(2000, 2000, |x, _| Luma([(x / 8) as u8]));
+        let counting = Counting { image: &image, reads: Cell::new(0) };
+        let region = Rectangle::new(200.0, 200.0, 1600.0, 1600.0);
+
+        predictor.run_downscaled(&counting, &region, 100.0);
+
+        // 2 cascades sample 2 pixels each, and each covers 16 by 16 pixels of the image
+        assert_eq!(counting.reads.get(), 4 * 16 * 16);
+    }
+
+    #[test]
+    fn maps_back_to_image() {
+        let predictor = mean_shape_predictor(vec![0.25, 0.5, 0.75, 0.25]);
+
+        let image = GrayImage::new(1000, 800);
+        let region = Rectangle::new(300.0, 200.0, 400.0, 400.0);
+
+        let direct = predictor.run(&image, &region);
+        let downscaled = predictor.run_downscaled(&image, &region, 100.0);
+
+        for (a, b) in direct.iter().zip(&downscaled) {
+            assert!((a - b).norm() < 1e-2);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use {ShapePredictor, IntensitySource, Rectangle, Vector2, Error, median};
+
+/// How the landmarks from each shape predictor in an ensemble are combined.
+#[derive(Debug, Clone)]
+pub enum Combination {
+    Mean,
+    /// The median of the x and y coordinates of each landmark, which ignores a single model failing.
+    Median,
+    /// A weighted mean with a weight for each landmark of each model, indexed as `weights[model][part]`.
+    /// The weights for each landmark are normalised so that they sum to 1.
+    Weighted(Vec<Vec<f32>>)
+}
+
+/// Several shape predictors with the same landmark layout that are run on the same region and combined.
+pub struct Ensemble {
+    predictors: Vec<ShapePredictor>,
+    combination: Combination
+}
+
+impl Ensemble {
+    /// Create an ensemble, checking that every shape predictor (and every set of weights) has the same number of parts.
+    pub fn new(predictors: Vec<ShapePredictor>, combination: Combination) -> Result<Self, Error> {
+        let parts = match predictors.first() {
+            Some(predictor) => predictor.num_parts(),
+            None => return Err(Error::InvalidEnsemble("an ensemble needs at least one shape predictor"))
+        };
+
+        for predictor in &predictors {
+            if predictor.num_parts() != parts {
+                return Err(Error::MismatchedParts { expected: parts, found: predictor.num_parts() });
+            }
+        }
+
+        if let Combination::Weighted(ref weights) = combination {
+            if weights.len() != predictors.len() {
+                return Err(Error::InvalidEnsemble("there must be one set of weights per shape predictor"));
+            }
+
+            for model_weights in weights {
+                if model_weights.len() != parts {
+                    return Err(Error::MismatchedParts { expected: parts, found: model_weights.len() });
+                }
+            }
+
+            for part in 0 .. parts {
+                if weights.iter().map(|model_weights| model_weights[part]).sum::<f32>() <= 0.0 {
+                    return Err(Error::InvalidEnsemble("the weights for each part must sum to more than 0"));
+                }
+            }
+        }
+
+        Ok(Self {
+            predictors, combination
+        })
+    }
+
+    pub fn predictors(&self) -> &[ShapePredictor] {
+        &self.predictors
+    }
+
+    /// The number of landmarks that each shape predictor finds.
+    pub fn num_parts(&self) -> usize {
+        self.predictors[0].num_parts()
+    }
+
+    /// Run every shape predictor on the region and combine the results.
+    pub fn run<I: IntensitySource>(&self, image: &I, region: &Rectangle) -> Vec<Vector2> {
+        let runs: Vec<Vec<Vector2>> = self.predictors.iter()
+            .map(|predictor| predictor.run(image, region))
+            .collect();
+
+        (0 .. self.num_parts())
+            .map(|part| match self.combination {
+                Combination::Mean => {
+                    runs.iter().fold(Vector2::new(0.0, 0.0), |sum, run| sum + run[part]) / runs.len() as f32
+                },
+                Combination::Median => Vector2::new(
+                    median(runs.iter().map(|run| run[part].x).collect()),
+                    median(runs.iter().map(|run| run[part].y).collect())
+                ),
+                Combination::Weighted(ref weights) => {
+                    let total = weights.iter().map(|model_weights| model_weights[part]).sum::<f32>();
+
+                    runs.iter().zip(weights)
+                        .fold(Vector2::new(0.0, 0.0), |sum, (run, model_weights)| sum + run[part] * model_weights[part]) / total
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ensemble::*;
+    use test_predictors::mean_shape_predictor;
+    use image::GrayImage;
+    use std::f32;
+
+    fn predictors() -> Vec<ShapePredictor> {
+        [0.2, 0.3, 0.7].iter().map(|&x| mean_shape_predictor(vec![x, 0.5])).collect()
+    }
+
+    #[test]
+    fn combinations() {
+        let image = GrayImage::new(10, 10);
+        let region = Rectangle::new(0.0, 0.0, 10.0, 10.0);
+
+        let mean = Ensemble::new(predictors(), Combination::Mean).unwrap().run(&image, &region);
+        assert!((mean[0] - Vector2::new(4.0, 5.0)).norm() < 1e-4);
+
+        let median = Ensemble::new(predictors(), Combination::Median).unwrap().run(&image, &region);
+        assert!((median[0] - Vector2::new(3.0, 5.0)).norm() < 1e-4);
+
+        let weighted = Ensemble::new(predictors(), Combination::Weighted(vec![vec![1.0], vec![0.0], vec![3.0]])).unwrap();
+        assert!((weighted.run(&image, &region)[0] - Vector2::new(5.75, 5.0)).norm() < 1e-4);
+    }
+
+    #[test]
+    fn invalid_ensembles() {
+        let mut mismatched = predictors();
+        mismatched.push(mean_shape_predictor(vec![0.25, 0.5, 0.75, 0.5]));
+
+        assert!(Ensemble::new(Vec::new(), Combination::Mean).is_err());
+        assert!(Ensemble::new(mismatched, Combination::Mean).is_err());
+        assert!(Ensemble::new(predictors(), Combination::Weighted(vec![vec![1.0]; 2])).is_err());
+        assert!(Ensemble::new(predictors(), Combination::Weighted(vec![vec![1.0, 1.0]; 3])).is_err());
+        assert!(Ensemble::new(predictors(), Combination::Weighted(vec![vec![0.0]; 3])).is_err());
+    }
+
+    #[test]
+    fn median_ignores_nan() {
+        assert_eq!(median(vec![3.0, f32::NAN, 1.0, 2.0]), 2.0);
+        assert_eq!(median(vec![4.0, 1.0, f32::NAN, 2.0, 3.0]), 2.5);
+        assert!(median(vec![f32::NAN]).is_nan());
+    }
+}
@@ -0,0 +1,722 @@
+use std::fs::File;
+use std::io::Read;
+use std::cmp::Ordering;
+use dlib_parser::{self, FhogScanner};
+use {IntensitySource, Rectangle, Error};
+
+// A port of dlib's `object_detector<scan_fhog_pyramid<pyramid_down<6>>>`, the type of `get_frontal_face_detector()`.
+// https://github.com/davisking/dlib/blob/master/dlib/image_processing/frontal_face_detector.h
+
+// The number of features per cell: 18 contrast sensitive orientations, 9 contrast insensitive orientations
+// and 4 texture features.
+const NUM_PLANES: usize = 31;
+
+// The downsampling factor of each pyramid level is `(PYRAMID_DOWN - 1) / PYRAMID_DOWN`.
+const PYRAMID_DOWN: f64 = 6.0;
+
+/// An object found by a `HogDetector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    /// The bounding box of the object, in the same form as dlib so that it can be passed straight to
+    /// `ShapePredictor::run`.
+    pub rect: Rectangle,
+    /// How far above the detection threshold the object scored.
+    pub confidence: f32,
+    /// The index of the filter that found the object. The dlib frontal face detector has filters for frontal,
+    /// left and right facing and rotated faces.
+    pub weight_index: usize
+}
+
+// A rectangle with inclusive integer bounds, as used by dlib.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DlibRect {
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64
+}
+
+impl DlibRect {
+    fn centered(x: i64, y: i64, width: i64, height: i64) -> Self {
+        let left = x - width / 2;
+        let top = y - height / 2;
+
+        Self {
+            left, top,
+            right: left + width - 1,
+            bottom: top + height - 1
+        }
+    }
+
+    fn width(&self) -> i64 {
+        self.right - self.left + 1
+    }
+
+    fn height(&self) -> i64 {
+        self.bottom - self.top + 1
+    }
+
+    fn area(&self) -> f64 {
+        if self.right < self.left || self.bottom < self.top {
+            0.0
+        } else {
+            (self.width() * self.height()) as f64
+        }
+    }
+
+    fn intersect(&self, other: &DlibRect) -> Self {
+        Self {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom)
+        }
+    }
+
+    fn union(&self, other: &DlibRect) -> Self {
+        Self {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom)
+        }
+    }
+
+    fn map_corners<F: Fn(f64, f64) -> (f64, f64)>(&self, function: F) -> Self {
+        let (left, top) = function(self.left as f64, self.top as f64);
+        let (right, bottom) = function(self.right as f64, self.bottom as f64);
+
+        // dlib rounds when converting from floating point to integer points
+        Self {
+            left: (left + 0.5).floor() as i64,
+            top: (top + 0.5).floor() as i64,
+            right: (right + 0.5).floor() as i64,
+            bottom: (bottom + 0.5).floor() as i64
+        }
+    }
+
+    // https://github.com/davisking/dlib/blob/master/dlib/image_transforms/image_pyramid.h
+    fn pyramid_down(&self) -> Self {
+        self.map_corners(|x, y| {
+            let ratio = (PYRAMID_DOWN - 1.0) / PYRAMID_DOWN;
+            ((x - 0.3) * ratio, (y - 0.3) * ratio)
+        })
+    }
+
+    fn pyramid_up(&self, levels: usize) -> Self {
+        self.map_corners(|mut x, mut y| {
+            let ratio = PYRAMID_DOWN / (PYRAMID_DOWN - 1.0);
+
+            for _ in 0 .. levels {
+                x = x * ratio + 0.3;
+                y = y * ratio + 0.3;
+            }
+
+            (x, y)
+        })
+    }
+
+    // Map a rectangle in the image to fHOG cells.
+    // https://github.com/davisking/dlib/blob/master/dlib/image_transforms/fhog.h
+    fn image_to_fhog(&self, cell_size: i64, rows_padding: i64, cols_padding: i64) -> Self {
+        // There is a one pixel border around the image and a one cell border around the features
+        let point = |x: i64, y: i64| (
+            (x - 1) / cell_size - 1 + (cols_padding - 1) / 2,
+            (y - 1) / cell_size - 1 + (rows_padding - 1) / 2
+        );
+
+        let (left, top) = point(self.left, self.top);
+        let (right, bottom) = point(self.right, self.bottom);
+
+        Self {
+            left, top, right, bottom
+        }
+    }
+
+    // Map a rectangle of fHOG cells to the image, placing the corners at the centers of the cells.
+    fn fhog_to_image(&self, cell_size: i64, rows_padding: i64, cols_padding: i64) -> Self {
+        let point = |x: i64, y: i64| {
+            let x = (x + 1 - (cols_padding - 1) / 2) * cell_size + 1;
+            let y = (y + 1 - (rows_padding - 1) / 2) * cell_size + 1;
+            let half = cell_size / 2;
+
+            (
+                if x >= 0 { x + half } else { x - half },
+                if y >= 0 { y + half } else { y - half }
+            )
+        };
+
+        let (left, top) = point(self.left, self.top);
+        let (right, bottom) = point(self.right, self.bottom);
+
+        Self {
+            left, top, right, bottom
+        }
+    }
+
+    fn to_rectangle(self) -> Rectangle {
+        // The shape predictor maps the unit square onto the top left and bottom right pixels of a dlib
+        // rectangle, so the width doesn't include the last pixel
+        Rectangle::new(
+            self.left as f32, self.top as f32,
+            (self.right - self.left) as f32, (self.bottom - self.top) as f32
+        )
+    }
+}
+
+// A grayscale image or a single plane of features, stored row by row.
+#[derive(Debug, Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f32>
+}
+
+impl Plane {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width, height,
+            data: vec![0.0; width * height]
+        }
+    }
+
+    fn from_source<I: IntensitySource>(image: &I) -> Self {
+        let mut plane = Self::new(image.width() as usize, image.height() as usize);
+
+        for y in 0 .. plane.height {
+            for x in 0 .. plane.width {
+                plane.data[y * plane.width + x] = image.intensity(x as u32, y as u32).floor();
+            }
+        }
+
+        plane
+    }
+
+    fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    // Downsample by 5/6 with bilinear interpolation, like dlib's `pyramid_down<6>`.
+    fn pyramid_down(&self) -> Self {
+        let ratio = (PYRAMID_DOWN - 1.0) / PYRAMID_DOWN;
+        let width = (ratio * self.width as f64) as usize;
+        let height = (ratio * self.height as f64) as usize;
+        let mut down = Self::new(width, height);
+
+        let x_scale = (self.width as f64 - 1.0) / (width.max(2) - 1) as f64;
+        let y_scale = (self.height as f64 - 1.0) / (height.max(2) - 1) as f64;
+
+        for y in 0 .. height {
+            let source_y = y as f64 * y_scale;
+            let top = (source_y.floor() as usize).min(self.height - 1);
+            let bottom = (top + 1).min(self.height - 1);
+            let tb_frac = (source_y - top as f64) as f32;
+
+            for x in 0 .. width {
+                let source_x = x as f64 * x_scale;
+                let left = (source_x.floor() as usize).min(self.width - 1);
+                let right = (left + 1).min(self.width - 1);
+                let lr_frac = (source_x - left as f64) as f32;
+
+                let value =
+                    (1.0 - tb_frac) * ((1.0 - lr_frac) * self.get(left, top) + lr_frac * self.get(right, top)) +
+                    tb_frac * ((1.0 - lr_frac) * self.get(left, bottom) + lr_frac * self.get(right, bottom));
+
+                // The pyramid is made of 8-bit images in dlib
+                down.data[y * width + x] = value.clamp(0.0, 255.0).floor();
+            }
+        }
+
+        down
+    }
+}
+
+// The unit vectors of the 9 contrast insensitive orientations
+const DIRECTIONS: [(f64, f64); 9] = [
+    (1.0000, 0.0000),
+    (0.9397, 0.3420),
+    (0.7660, 0.6428),
+    (0.500, 0.8660),
+    (0.1736, 0.9848),
+    (-0.1736, 0.9848),
+    (-0.5000, 0.8660),
+    (-0.7660, 0.6428),
+    (-0.9397, 0.3420)
+];
+
+// Extract Felzenszwalb's HOG features from an image, following dlib's `extract_fhog_features`.
+// The features are padded with zeros so that a filter of `rows_padding` by `cols_padding` cells can be
+// centered on every cell.
+fn extract_fhog_features(image: &Plane, cell_size: usize, rows_padding: usize, cols_padding: usize) -> Vec<Plane> {
+    let cells_height = (image.height as f64 / cell_size as f64 + 0.5) as usize;
+    let cells_width = (image.width as f64 / cell_size as f64 + 0.5) as usize;
+
+    if cells_height < 3 || cells_width < 3 {
+        return Vec::new();
+    }
+
+    // The histograms have a border of one cell so that the interpolation doesn't need bounds checks
+    let hist_width = cells_width + 2;
+    let mut hist = vec![[0.0_f32; 18]; (cells_height + 2) * hist_width];
+
+    let visible_height = (cells_height * cell_size).min(image.height) - 1;
+    let visible_width = (cells_width * cell_size).min(image.width) - 1;
+
+    for y in 1 .. visible_height {
+        let yp = (y as f64 + 0.5) / cell_size as f64 - 0.5;
+        let iyp = yp.floor();
+        let vy0 = yp - iyp;
+        let vy1 = 1.0 - vy0;
+        let iyp = (iyp + 1.0) as usize;
+
+        for x in 1 .. visible_width {
+            let grad_x = (image.get(x + 1, y) - image.get(x - 1, y)) as f64;
+            let grad_y = (image.get(x, y + 1) - image.get(x, y - 1)) as f64;
+
+            // Snap to one of 18 orientations
+            let mut best_dot = 0.0;
+            let mut best_o = 0;
+
+            for (o, &(dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let dot = dx * grad_x + dy * grad_y;
+
+                if dot > best_dot {
+                    best_dot = dot;
+                    best_o = o;
+                } else if -dot > best_dot {
+                    best_dot = -dot;
+                    best_o = o + 9;
+                }
+            }
+
+            let v = (grad_x * grad_x + grad_y * grad_y).sqrt();
+
+            // Add to the 4 histograms around the pixel with bilinear interpolation
+            let xp = (x as f64 + 0.5) / cell_size as f64 - 0.5;
+            let ixp = xp.floor();
+            let vx0 = xp - ixp;
+            let vx1 = 1.0 - vx0;
+            let ixp = (ixp + 1.0) as usize;
+
+            hist[iyp * hist_width + ixp][best_o] += (vy1 * vx1 * v) as f32;
+            hist[(iyp + 1) * hist_width + ixp][best_o] += (vy0 * vx1 * v) as f32;
+            hist[iyp * hist_width + ixp + 1][best_o] += (vy1 * vx0 * v) as f32;
+            hist[(iyp + 1) * hist_width + ixp + 1][best_o] += (vy0 * vx0 * v) as f32;
+        }
+    }
+
+    // The energy of each cell, summed over the contrast insensitive orientations
+    let mut norm = Plane::new(cells_width, cells_height);
+
+    for y in 0 .. cells_height {
+        for x in 0 .. cells_width {
+            let cell = &hist[(y + 1) * hist_width + x + 1];
+            norm.data[y * cells_width + x] = (0 .. 9).map(|o| (cell[o] + cell[o + 9]).powi(2)).sum();
+        }
+    }
+
+    let hog_height = cells_height - 2;
+    let hog_width = cells_width - 2;
+    let rows_offset = (rows_padding - 1) / 2;
+    let cols_offset = (cols_padding - 1) / 2;
+    let mut features = vec![Plane::new(hog_width + cols_padding - 1, hog_height + rows_padding - 1); NUM_PLANES];
+
+    let eps = 0.0001;
+
+    for y in 0 .. hog_height {
+        for x in 0 .. hog_width {
+            let n = |dx: usize, dy: usize| norm.get(x + dx, y + dy);
+
+            // Normalise the cell by the energy of the 4 blocks of 2x2 cells that contain it
+            let blocks = [
+                n(1, 1) + n(2, 1) + n(1, 2) + n(2, 2),
+                n(1, 0) + n(2, 0) + n(1, 1) + n(2, 1),
+                n(0, 1) + n(1, 1) + n(0, 2) + n(1, 2),
+                n(0, 0) + n(1, 0) + n(0, 1) + n(1, 1)
+            ];
+
+            let mut nn = [0.0; 4];
+            let mut scale = [0.0; 4];
+
+            for i in 0 .. 4 {
+                nn[i] = 0.2 * (blocks[i] + eps).sqrt();
+                scale[i] = 0.1 / nn[i];
+            }
+
+            let normalise = |value: f32| (0 .. 4).map(|i| value.min(nn[i]) * scale[i]).sum::<f32>();
+
+            let cell = &hist[(y + 2) * hist_width + x + 2];
+            let index = (y + rows_offset) * features[0].width + x + cols_offset;
+            let mut texture = [0.0; 4];
+
+            // Contrast sensitive features
+            for o in 0 .. 18 {
+                features[o].data[index] = normalise(cell[o]);
+
+                for i in 0 .. 4 {
+                    texture[i] += cell[o].min(nn[i]) * scale[i];
+                }
+            }
+
+            // Contrast insensitive features
+            for o in 0 .. 9 {
+                features[o + 18].data[index] = normalise(cell[o] + cell[o + 9]);
+            }
+
+            // Texture features
+            for i in 0 .. 4 {
+                features[27 + i].data[index] = texture[i] * 2.0 * 0.2357;
+            }
+        }
+    }
+
+    features
+}
+
+// A linear filter over fHOG features, with a plane of weights for each feature.
+#[derive(Debug, Clone)]
+struct Filter {
+    planes: Vec<Plane>,
+    threshold: f32
+}
+
+/// A sliding window object detector over a pyramid of fHOG features, compatible with dlib's
+/// `get_frontal_face_detector()` and other detectors trained with `scan_fhog_pyramid<pyramid_down<6>>`.
+#[derive(Debug, Clone)]
+pub struct HogDetector {
+    scanner: FhogScanner,
+    filter_width: i64,
+    filter_height: i64,
+    iou_thresh: f64,
+    percent_covered_thresh: f64,
+    filters: Vec<Filter>
+}
+
+impl HogDetector {
+    /// Deserialize a detector from a file encoded by dlib, e.g. with
+    /// `dlib::serialize("detector.svm") << dlib::get_frontal_face_detector();`.
+    pub fn read_from_dlib(filename: &str) -> Result<Self, Error> {
+        let mut buffer = Vec::new();
+        File::open(filename)?.read_to_end(&mut buffer)?;
+        Self::from_dlib_bytes(&buffer)
+    }
+
+    /// Deserialize a detector from bytes encoded by dlib.
+    pub fn from_dlib_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let detector = dlib_parser::parse_object_detector(bytes)?;
+        let scanner = detector.scanner;
+
+        if scanner.cell_size <= 0 || scanner.window_width <= 0 || scanner.window_height <= 0 || scanner.padding < 0 {
+            return Err(Error::InvalidDetector("the detection window is empty"));
+        }
+
+        // https://github.com/davisking/dlib/blob/master/dlib/image_processing/scan_fhog_pyramid.h
+        let window = DlibRect::centered(0, 0, scanner.window_width, scanner.window_height)
+            .image_to_fhog(scanner.cell_size, 1, 1);
+        let filter_width = window.width() + scanner.padding * 2;
+        let filter_height = window.height() + scanner.padding * 2;
+        let size = (filter_width * filter_height) as usize;
+
+        let mut filters = Vec::with_capacity(detector.weights.len());
+
+        for weights in &detector.weights {
+            // The weights of each feature plane, followed by the detection threshold
+            if weights.len() != NUM_PLANES * size + 1 {
+                return Err(Error::InvalidDetector("the weight vectors don't match the detection window"));
+            }
+
+            let planes = weights[.. NUM_PLANES * size].chunks(size)
+                .map(|plane| Plane {
+                    width: filter_width as usize,
+                    height: filter_height as usize,
+                    data: plane.iter().map(|weight| *weight as f32).collect()
+                })
+                .collect();
+
+            filters.push(Filter {
+                planes,
+                threshold: weights[NUM_PLANES * size] as f32
+            });
+        }
+
+        Ok(Self {
+            scanner, filter_width, filter_height, filters,
+            iou_thresh: detector.iou_thresh,
+            percent_covered_thresh: detector.percent_covered_thresh
+        })
+    }
+
+    /// Find objects in an image, sorted from the most to the least confident.
+    pub fn detect<I: IntensitySource>(&self, image: &I) -> Vec<Detection> {
+        self.detect_with_threshold(image, 0.0)
+    }
+
+    /// Find objects in an image, with a threshold that is added to the threshold the detector was trained with.
+    /// Negative values find more objects at the cost of more false positives.
+    pub fn detect_with_threshold<I: IntensitySource>(&self, image: &I, adjust_threshold: f32) -> Vec<Detection> {
+        let pyramid = self.feature_pyramid(image);
+        let mut detections = Vec::new();
+
+        for (weight_index, filter) in self.filters.iter().enumerate() {
+            for (level, features) in pyramid.iter().enumerate() {
+                self.scan(features, filter, level, weight_index, filter.threshold + adjust_threshold, &mut detections);
+            }
+        }
+
+        // Non-maximum suppression, ignoring detections from filters with NaN weights
+        detections.retain(|(_, detection)| !detection.confidence.is_nan());
+        detections.sort_by(|a, b| b.1.confidence.partial_cmp(&a.1.confidence).unwrap_or(Ordering::Equal));
+        let mut kept: Vec<(DlibRect, Detection)> = Vec::new();
+
+        for (rect, detection) in detections {
+            if !kept.iter().any(|(other, _)| self.boxes_overlap(&rect, other)) {
+                kept.push((rect, detection));
+            }
+        }
+
+        kept.into_iter().map(|(_, detection)| detection).collect()
+    }
+
+    fn feature_pyramid<I: IntensitySource>(&self, image: &I) -> Vec<Vec<Plane>> {
+        let scanner = &self.scanner;
+        let mut image = Plane::from_source(image);
+
+        // Count the levels that are at least the minimum size
+        let mut rect = DlibRect {
+            left: 0,
+            top: 0,
+            right: image.width as i64 - 1,
+            bottom: image.height as i64 - 1
+        };
+        let mut levels = 0;
+
+        loop {
+            rect = rect.pyramid_down();
+            levels += 1;
+
+            if rect.width() < scanner.min_pyramid_layer_width || rect.height() < scanner.min_pyramid_layer_height ||
+               levels >= scanner.max_pyramid_levels {
+                break;
+            }
+        }
+
+        let mut pyramid = Vec::with_capacity(levels as usize);
+
+        for level in 0 .. levels {
+            if level > 0 {
+                image = image.pyramid_down();
+            }
+
+            pyramid.push(extract_fhog_features(&image, scanner.cell_size as usize, self.filter_height as usize, self.filter_width as usize));
+        }
+
+        pyramid
+    }
+
+    // Slide a filter over the features of one pyramid level and record everything that scores above the threshold.
+    fn scan(&self, features: &[Plane], filter: &Filter, level: usize, weight_index: usize, threshold: f32, detections: &mut Vec<(DlibRect, Detection)>) {
+        if features.is_empty() {
+            return;
+        }
+
+        let width = features[0].width;
+        let height = features[0].height;
+        let filter_width = self.filter_width as usize;
+        let filter_height = self.filter_height as usize;
+
+        if width < filter_width || height < filter_height {
+            return;
+        }
+
+        // The filter is centered on each position, so positions near the edges are skipped
+        for top in 0 .. height - filter_height + 1 {
+            for left in 0 .. width - filter_width + 1 {
+                let mut score = 0.0;
+
+                for (plane, weights) in features.iter().zip(&filter.planes) {
+                    for y in 0 .. filter_height {
+                        let row = &plane.data[(top + y) * width + left ..][.. filter_width];
+                        let weight_row = &weights.data[y * filter_width ..][.. filter_width];
+                        score += row.iter().zip(weight_row).map(|(a, b)| a * b).sum::<f32>();
+                    }
+                }
+
+                if score >= threshold {
+                    let x = (left + filter_width / 2) as i64;
+                    let y = (top + filter_height / 2) as i64;
+
+                    // The box covers the detection window, without the padding around the filter
+                    let padding = self.scanner.padding;
+                    let rect = DlibRect::centered(x, y, self.filter_width - 2 * padding, self.filter_height - 2 * padding)
+                        .fhog_to_image(self.scanner.cell_size, self.filter_height, self.filter_width)
+                        .pyramid_up(level);
+
+                    detections.push((rect, Detection {
+                        rect: rect.to_rectangle(),
+                        confidence: score - filter.threshold,
+                        weight_index
+                    }));
+                }
+            }
+        }
+    }
+
+    // https://github.com/davisking/dlib/blob/master/dlib/image_processing/box_overlap_testing.h
+    fn boxes_overlap(&self, a: &DlibRect, b: &DlibRect) -> bool {
+        let inner = a.intersect(b).area();
+
+        if inner == 0.0 {
+            return false;
+        }
+
+        inner / a.union(b).area() > self.iou_thresh ||
+        inner / a.area() > self.percent_covered_thresh ||
+        inner / b.area() > self.percent_covered_thresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hog::*;
+    use image::{GrayImage, Luma};
+
+    // https://github.com/davisking/dlib/blob/master/dlib/serialize.h#L230
+    fn int(bytes: &mut Vec<u8>, int: i64) {
+        let mut magnitude = int.abs();
+        let mut buf = Vec::new();
+
+        while magnitude != 0 {
+            buf.push((magnitude & 0xFF) as u8);
+            magnitude >>= 8;
+        }
+
+        bytes.push(buf.len() as u8 | if int < 0 { 0x80 } else { 0 });
+        bytes.extend(buf);
+    }
+
+    // Doubles are written as `mantissa * 2^exponent`, which is exact for the values used here
+    fn double(bytes: &mut Vec<u8>, value: f64) {
+        let mut mantissa = value;
+        let mut exponent = 0;
+
+        while mantissa.fract() != 0.0 {
+            mantissa *= 2.0;
+            exponent -= 1;
+        }
+
+        int(bytes, mantissa as i64);
+        int(bytes, exponent);
+    }
+
+    // A detector with a 16x16 pixel window, 8 pixel cells and a padding of one cell, so the filter is
+    // 4x4 cells. The texture features are rewarded in the window and slightly penalised in the padding.
+    fn padded_detector() -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        // object_detector version
+        int(&mut bytes, 2);
+
+        // scan_fhog_pyramid version
+        int(&mut bytes, 1);
+
+        // A cached feature pyramid with one level of one 1x2 plane
+        int(&mut bytes, 1);
+        int(&mut bytes, 1);
+        int(&mut bytes, 1);
+        int(&mut bytes, 1);
+        int(&mut bytes, -1);
+        int(&mut bytes, -2);
+        double(&mut bytes, 0.5);
+        double(&mut bytes, -0.25);
+
+        // cell_size, padding, window_width, window_height, max_pyramid_levels, min_pyramid_layer_width/height
+        for &value in &[8, 1, 16, 16, 1, 16, 16] {
+            int(&mut bytes, value);
+        }
+
+        // Nuclear norm regularization strength
+        double(&mut bytes, 0.0);
+
+        // iou_thresh and percent_covered_thresh
+        double(&mut bytes, 0.5);
+        double(&mut bytes, 1.0);
+
+        // One weight vector: 31 planes of 4x4 weights and the threshold
+        int(&mut bytes, 1);
+        int(&mut bytes, -(NUM_PLANES as i64 * 16 + 1));
+        int(&mut bytes, -1);
+
+        for plane in 0 .. NUM_PLANES {
+            for y in 0 .. 4 {
+                for x in 0 .. 4 {
+                    let inside = (1 .. 3).contains(&x) && (1 .. 3).contains(&y);
+
+                    double(&mut bytes, match (plane >= 27, inside) {
+                        (false, _) => 0.0,
+                        (true, true) => 1.0,
+                        (true, false) => -0.25
+                    });
+                }
+            }
+        }
+
+        double(&mut bytes, 0.5);
+
+        bytes
+    }
+
+    #[test]
+    fn fhog_mapping() {
+        // An 80x80 window with 8 pixel cells covers 10x10 cells
+        let window = DlibRect::centered(0, 0, 80, 80).image_to_fhog(8, 1, 1);
+        assert_eq!((window.width(), window.height()), (10, 10));
+
+        let cells = DlibRect::centered(20, 20, 10, 10);
+        let image = cells.fhog_to_image(8, 10, 10);
+        assert_eq!(image.image_to_fhog(8, 10, 10), cells);
+    }
+
+    #[test]
+    fn fhog_features() {
+        // A vertical edge should give strong horizontal gradients
+        let mut image = Plane::new(64, 64);
+        for y in 0 .. 64 {
+            for x in 32 .. 64 {
+                image.data[y * 64 + x] = 255.0;
+            }
+        }
+
+        let features = extract_fhog_features(&image, 8, 1, 1);
+        assert_eq!(features.len(), NUM_PLANES);
+        assert_eq!((features[0].width, features[0].height), (6, 6));
+        assert!(features[0].get(2, 2) > 0.0);
+        assert_eq!(features[4].get(2, 2), 0.0);
+    }
+
+    #[test]
+    fn padded_detector_boxes() {
+        let bytes = padded_detector();
+
+        let parsed = dlib_parser::parse_object_detector(&bytes).unwrap();
+        assert_eq!((parsed.scanner.cell_size, parsed.scanner.padding), (8, 1));
+        assert_eq!((parsed.scanner.window_width, parsed.scanner.window_height), (16, 16));
+        assert_eq!((parsed.iou_thresh, parsed.percent_covered_thresh), (0.5, 1.0));
+        assert_eq!(parsed.weights.len(), 1);
+        assert_eq!(parsed.weights[0][NUM_PLANES * 16], 0.5);
+
+        let detector = HogDetector::from_dlib_bytes(&bytes).unwrap();
+        assert_eq!((detector.filter_width, detector.filter_height), (4, 4));
+
+        // A checkerboard covering 2x2 cells
+        let image = GrayImage::from_fn(96, 96, |x, y| {
+            Luma([if (40 .. 56).contains(&x) && (40 .. 56).contains(&y) && (x / 4 + y / 4) % 2 == 0 { 255 } else { 0 }])
+        });
+
+        let detections = detector.detect(&image);
+        assert!(!detections.is_empty());
+
+        // The box covers the 2x2 cells of the window, not the 4x4 cells of the padded filter, and
+        // its corners are at the centers of the cells over the pattern
+        assert_eq!(detections[0].rect, Rectangle::new(45.0, 45.0, 8.0, 8.0));
+    }
+}
@@ -0,0 +1,222 @@
+use image::{GenericImage, Pixel};
+
+/// A source of pixel intensities that the shape predictor can sample from.
+pub trait IntensitySource {
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    /// Get the intensity of a pixel on the scale that the model was trained on.
+    /// `x` and `y` are always within the width and height of the source.
+    fn intensity(&self, x: u32, y: u32) -> f32;
+}
+
+/// A range of intensities, from black to white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntensityRange {
+    pub black: f32,
+    pub white: f32
+}
+
+impl IntensityRange {
+    pub fn new(black: f32, white: f32) -> Self {
+        Self {
+            black, white
+        }
+    }
+
+    /// The 8-bit range that dlib models are trained on.
+    pub fn model() -> Self {
+        Self::new(0.0, 255.0)
+    }
+
+    /// Map a value in this range to another range, clamping it to black or white.
+    pub fn map(&self, value: f32, to: &IntensityRange) -> f32 {
+        // Avoid rounding errors for images that are already in the right range (e.g. 8-bit images)
+        if self == to {
+            return value;
+        }
+
+        let t = (value - self.black) / (self.white - self.black);
+
+        if t.is_finite() {
+            to.black + t.clamp(0.0, 1.0) * (to.white - to.black)
+        } else {
+            to.black
+        }
+    }
+}
+
+/// A subpixel type that can be converted to an intensity.
+pub trait IntensityValue: Copy {
+    /// The full range of values of the type. Floating point images are expected to be between 0 and 1.
+    fn range() -> IntensityRange;
+
+    fn value(self) -> f32;
+}
+
+macro_rules! intensity_value {
+    ($ty:ty, $black:expr, $white:expr) => {
+        impl IntensityValue for $ty {
+            fn range() -> IntensityRange {
+                IntensityRange::new($black, $white)
+            }
+
+            fn value(self) -> f32 {
+                self as f32
+            }
+        }
+    }
+}
+
+intensity_value!(u8, 0.0, 255.0);
+intensity_value!(u16, 0.0, 65535.0);
+intensity_value!(u32, 0.0, 4294967295.0);
+intensity_value!(f32, 0.0, 1.0);
+intensity_value!(f64, 0.0, 1.0);
+
+fn raw_intensity<I: GenericImage>(image: &I, x: u32, y: u32) -> f32
+    where <I::Pixel as Pixel>::Subpixel: IntensityValue
+{
+    image.get_pixel(x, y).to_luma().data[0].value()
+}
+
+// Any `image` type is sampled by converting each pixel to luma and scaling it from the full range of its type.
+impl<I: GenericImage> IntensitySource for I
+    where <I::Pixel as Pixel>::Subpixel: IntensityValue
+{
+    fn width(&self) -> u32 {
+        self.dimensions().0
+    }
+
+    fn height(&self) -> u32 {
+        self.dimensions().1
+    }
+
+    fn intensity(&self, x: u32, y: u32) -> f32 {
+        let range = <<I::Pixel as Pixel>::Subpixel as IntensityValue>::range();
+        range.map(raw_intensity(self, x, y), &IntensityRange::model())
+    }
+}
+
+/// An image whose intensities are scaled from a custom range, such as a 16-bit thermal or medical
+/// capture that only uses part of the range of its type.
+#[derive(Debug, Clone, Copy)]
+pub struct Normalised<'a, I: 'a> {
+    image: &'a I,
+    input: IntensityRange,
+    model: IntensityRange
+}
+
+impl<'a, I: GenericImage> Normalised<'a, I>
+    where <I::Pixel as Pixel>::Subpixel: IntensityValue
+{
+    /// Scale the values of an image from `input` to the range that the model was trained on.
+    pub fn new(image: &'a I, input: IntensityRange) -> Self {
+        Self {
+            image, input,
+            model: IntensityRange::model()
+        }
+    }
+
+    /// Scale to a different range, for models that weren't trained on 8-bit images.
+    pub fn with_model_range(mut self, model: IntensityRange) -> Self {
+        self.model = model;
+        self
+    }
+}
+
+impl<'a, I: GenericImage> IntensitySource for Normalised<'a, I>
+    where <I::Pixel as Pixel>::Subpixel: IntensityValue
+{
+    fn width(&self) -> u32 {
+        self.image.dimensions().0
+    }
+
+    fn height(&self) -> u32 {
+        self.image.dimensions().1
+    }
+
+    fn intensity(&self, x: u32, y: u32) -> f32 {
+        self.input.map(raw_intensity(self.image, x, y), &self.model)
+    }
+}
+
+/// A borrowed 8-bit grayscale plane, such as a camera buffer or the Y plane of a NV12 or I420 frame.
+#[derive(Debug, Clone, Copy)]
+pub struct LumaBuffer<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    stride: usize
+}
+
+impl<'a> LumaBuffer<'a> {
+    /// Wrap a tightly packed buffer with one byte per pixel.
+    /// Returns `None` if the buffer is too small for the dimensions.
+    pub fn new(data: &'a [u8], width: u32, height: u32) -> Option<Self> {
+        Self::with_stride(data, width, height, width as usize)
+    }
+
+    /// Wrap a buffer where each row starts `stride` bytes after the previous one.
+    /// Returns `None` if the stride is smaller than the width or the buffer is too small for the dimensions.
+    pub fn with_stride(data: &'a [u8], width: u32, height: u32, stride: usize) -> Option<Self> {
+        // The last row doesn't need any padding after it
+        let required = if height == 0 {
+            0
+        } else {
+            stride * (height as usize - 1) + width as usize
+        };
+
+        if stride < width as usize || data.len() < required {
+            None
+        } else {
+            Some(Self {
+                data, width, height, stride
+            })
+        }
+    }
+}
+
+impl<'a> IntensitySource for LumaBuffer<'a> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn intensity(&self, x: u32, y: u32) -> f32 {
+        debug_assert!(x < self.width && y < self.height);
+        self.data[y as usize * self.stride + x as usize] as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use intensity::*;
+
+    #[test]
+    fn luma_buffer_stride() {
+        let data = [1, 2, 0, 3, 4];
+        let buffer = LumaBuffer::with_stride(&data, 2, 2, 3).unwrap();
+
+        assert_eq!(buffer.intensity(1, 0), 2.0);
+        assert_eq!(buffer.intensity(0, 1), 3.0);
+        assert_eq!(buffer.intensity(1, 1), 4.0);
+
+        assert!(LumaBuffer::with_stride(&data, 3, 2, 2).is_none());
+        assert!(LumaBuffer::new(&data, 2, 3).is_none());
+    }
+
+    #[test]
+    fn range_mapping() {
+        let model = IntensityRange::model();
+
+        assert_eq!(u16::range().map(65535.0, &model), 255.0);
+        assert_eq!(f32::range().map(0.5, &model), 127.5);
+        assert_eq!(f32::range().map(f32::NAN, &model), 0.0);
+        assert_eq!(IntensityRange::new(0.0, 4095.0).map(8000.0, &model), 255.0);
+    }
+}
@@ -0,0 +1,108 @@
+use std::mem::size_of;
+use {ShapePredictor, RegressionTree, SplitFeature, Matrix, Vector2, location};
+
+impl ShapePredictor {
+    /// The number of landmarks that the shape predictor finds.
+    pub fn num_parts(&self) -> usize {
+        self.initial_shape.len() / 2
+    }
+
+    /// The number of cascades that are run.
+    pub fn cascade_depth(&self) -> usize {
+        self.forests.len()
+    }
+
+    /// The number of regression trees in each cascade.
+    pub fn trees_per_cascade(&self) -> Vec<usize> {
+        self.forests.iter().map(Vec::len).collect()
+    }
+
+    /// The depth of the deepest regression tree. A tree with a depth of `n` has `2^n` leaves.
+    pub fn tree_depth(&self) -> usize {
+        self.forests.iter()
+            .flat_map(|forest| forest.iter())
+            .map(|tree| {
+                // The trees are complete binary trees
+                let mut depth = 0;
+                while (1 << depth) < tree.leaf_values.len() {
+                    depth += 1;
+                }
+                depth
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The number of pixels sampled in each cascade.
+    pub fn feature_pool_sizes(&self) -> Vec<usize> {
+        self.deltas.iter().map(Vec::len).collect()
+    }
+
+    /// The mean shape that prediction starts from, normalised so that the region of interest is the unit square.
+    pub fn mean_shape(&self) -> Vec<Vector2> {
+        (0 .. self.num_parts())
+            .map(|i| location(&self.initial_shape, i as u64))
+            .collect()
+    }
+
+    /// The number of times each landmark is used as an anchor for a sampled pixel, across all cascades.
+    /// Anchors that aren't a landmark of the shape predictor, which only a malformed model has, aren't counted.
+    pub fn anchor_usage(&self) -> Vec<usize> {
+        let mut usage = vec![0; self.num_parts()];
+
+        for &anchor in self.anchor_idx.iter().flat_map(|anchors| anchors.iter()) {
+            if let Some(count) = usage.get_mut(anchor as usize) {
+                *count += 1;
+            }
+        }
+
+        usage
+    }
+
+    /// An estimate of the memory used by the shape predictor in bytes, not counting unused vector capacity.
+    pub fn memory_footprint(&self) -> usize {
+        let tree_size = |tree: &RegressionTree| {
+            size_of::<RegressionTree>() +
+            tree.splits.len() * size_of::<SplitFeature>() +
+            tree.leaf_values.iter().map(matrix_size).sum::<usize>()
+        };
+
+        let forests = self.forests.iter()
+            .map(|forest| size_of::<Vec<RegressionTree>>() + forest.iter().map(&tree_size).sum::<usize>())
+            .sum::<usize>();
+
+        let deltas = self.deltas.iter()
+            .map(|deltas| size_of::<Vec<Vector2>>() + deltas.len() * size_of::<Vector2>())
+            .sum::<usize>();
+
+        let anchors = self.anchor_idx.iter()
+            .map(|anchors| size_of::<Vec<u64>>() + anchors.len() * size_of::<u64>())
+            .sum::<usize>();
+
+        size_of::<ShapePredictor>() + matrix_size(&self.initial_shape) - size_of::<Matrix>() + forests + deltas + anchors
+    }
+}
+
+fn matrix_size(matrix: &Matrix) -> usize {
+    size_of::<Matrix>() + matrix.len() * size_of::<f32>()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_predictors::two_cascade_predictor;
+
+    #[test]
+    fn counts() {
+        let mut predictor = two_cascade_predictor();
+
+        assert_eq!(predictor.num_parts(), 2);
+        assert_eq!(predictor.cascade_depth(), 2);
+        assert_eq!(predictor.trees_per_cascade(), [1, 1]);
+        assert_eq!(predictor.tree_depth(), 1);
+        assert_eq!(predictor.feature_pool_sizes(), [2, 2]);
+        assert_eq!(predictor.anchor_usage(), [2, 2]);
+
+        predictor.anchor_idx[1] = vec![1, 7];
+        assert_eq!(predictor.anchor_usage(), [1, 2]);
+    }
+}
@@ -0,0 +1,125 @@
+use std::f32::consts::PI;
+use {IntensitySource, ShapePredictor, median, Rectangle, Vector2};
+
+/// How the region of interest is perturbed when running the predictor from several starts.
+#[derive(Debug, Clone)]
+pub struct JitterOptions {
+    /// The number of perturbed runs, in addition to the run on the unmodified region.
+    pub starts: usize,
+    /// The maximum shift of the region, as a fraction of its size.
+    pub translation: f32,
+    /// The maximum change in the size of the region, as a fraction of its size.
+    pub scale: f32
+}
+
+impl Default for JitterOptions {
+    fn default() -> Self {
+        Self {
+            starts: 8,
+            translation: 0.05,
+            scale: 0.05
+        }
+    }
+}
+
+/// The combined result of running the predictor from several perturbed regions.
+#[derive(Debug, Clone)]
+pub struct JitteredPrediction {
+    /// The per-landmark median of all the runs.
+    pub shape: Vec<Vector2>,
+    /// The mean distance of each landmark from the median, in pixels.
+    pub spread: Vec<f32>,
+    /// A score between 0 and 1. Runs that all converge to the same shape score close to 1, while runs that
+    /// just follow the perturbation of the region score around 0.5 or lower.
+    pub confidence: f32
+}
+
+impl ShapePredictor {
+    /// Run the shape predictor from several perturbed copies of the region and combine the results.
+    /// The perturbations are deterministic, so the same inputs always give the same prediction.
+    pub fn run_jittered<I: IntensitySource>(&self, image: &I, region: &Rectangle, options: &JitterOptions) -> JitteredPrediction {
+        let mut runs = vec![self.run(image, region)];
+
+        for i in 0 .. options.starts {
+            let angle = 2.0 * PI * i as f32 / options.starts as f32;
+            // Alternate between growing and shrinking the region
+            let scale = if i % 2 == 0 { 1.0 + options.scale } else { 1.0 - options.scale };
+
+            let offset = Vector2::new(angle.cos() * region.width, angle.sin() * region.height) * options.translation;
+
+            let rect = Rectangle::from_center(region.center() + offset, region.width * scale, region.height * scale);
+            runs.push(self.run(image, &rect));
+        }
+
+        let parts = runs[0].len();
+
+        let shape: Vec<Vector2> = (0 .. parts)
+            .map(|i| Vector2::new(
+                median(runs.iter().map(|run| run[i].x).collect()),
+                median(runs.iter().map(|run| run[i].y).collect())
+            ))
+            .collect();
+
+        let spread: Vec<f32> = (0 .. parts)
+            .map(|i| runs.iter().map(|run| (run[i] - shape[i]).norm()).sum::<f32>() / runs.len() as f32)
+            .collect();
+
+        let mean_spread = spread.iter().sum::<f32>() / parts.max(1) as f32;
+        // How far the regions were shifted or resized, which the spread is measured against
+        let perturbation = options.translation.max(options.scale) * (region.width * region.height).sqrt();
+
+        let confidence = if perturbation > 0.0 {
+            1.0 / (1.0 + mean_spread / perturbation)
+        } else {
+            1.0
+        };
+
+        JitteredPrediction {
+            shape, spread, confidence
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jitter::*;
+    use test_predictors::{two_cascade_predictor, mean_shape_predictor};
+    use image::{GrayImage, Luma};
+
+    #[test]
+    fn zero_jitter_matches_run() {
+        let predictor = two_cascade_predictor();
+        let image = GrayImage::from_fn(64, 64, |x, _| Luma([(x * 4) as u8]));
+        let region = Rectangle::new(8.0, 8.0, 48.0, 48.0);
+        let options = JitterOptions {
+            starts: 4,
+            translation: 0.0,
+            scale: 0.0
+        };
+
+        let prediction = predictor.run_jittered(&image, &region, &options);
+
+        assert_eq!(prediction.shape, predictor.run(&image, &region));
+        assert!(prediction.spread.iter().all(|&spread| spread == 0.0));
+        assert_eq!(prediction.confidence, 1.0);
+    }
+
+    #[test]
+    fn scaling_lowers_confidence() {
+        // The mean shape follows the region, so the runs only differ because of the change in scale
+        let predictor = mean_shape_predictor(vec![0.25, 0.5, 0.75, 0.5]);
+        let image = GrayImage::new(64, 64);
+        let region = Rectangle::new(8.0, 8.0, 48.0, 48.0);
+        let options = JitterOptions {
+            starts: 4,
+            translation: 0.0,
+            scale: 0.2
+        };
+
+        let prediction = predictor.run_jittered(&image, &region, &options);
+
+        assert!(prediction.spread.iter().all(|&spread| spread > 0.0));
+        assert!(prediction.confidence < 1.0);
+        assert!(prediction.confidence > 0.0);
+    }
+}
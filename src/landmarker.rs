@@ -0,0 +1,149 @@
+use image::GrayImage;
+#[cfg(feature = "rustface")]
+use rustface;
+use {ShapePredictor, HogDetector, BoxAdapter, Rectangle, Vector2};
+
+/// Something that finds objects in an image, such as a face detector.
+pub trait Detector {
+    /// Find objects in an image, returning the box and score of each.
+    fn detect(&mut self, image: &GrayImage) -> Vec<(Rectangle, f32)>;
+}
+
+impl Detector for HogDetector {
+    fn detect(&mut self, image: &GrayImage) -> Vec<(Rectangle, f32)> {
+        HogDetector::detect(self, image).into_iter()
+            .map(|detection| (detection.rect, detection.confidence))
+            .collect()
+    }
+}
+
+#[cfg(feature = "rustface")]
+impl Detector for Box<dyn rustface::Detector> {
+    fn detect(&mut self, image: &GrayImage) -> Vec<(Rectangle, f32)> {
+        let (width, height) = image.dimensions();
+        let data = rustface::ImageData::new(image, width, height);
+
+        rustface::Detector::detect(&mut **self, &data).iter()
+            .map(|face| {
+                let bbox = face.bbox();
+                let rect = Rectangle::new(bbox.x() as f32, bbox.y() as f32, bbox.width() as f32, bbox.height() as f32);
+                (rect, face.score() as f32)
+            })
+            .collect()
+    }
+}
+
+/// A face found by a `FaceLandmarker`.
+#[derive(Debug, Clone)]
+pub struct Face {
+    /// The box that the landmarks were found in, after any correction by the box adapter.
+    pub rect: Rectangle,
+    /// The score from the detector.
+    pub score: f32,
+    pub landmarks: Vec<Vector2>
+}
+
+/// A pipeline that detects faces, corrects their boxes and finds their landmarks.
+pub struct FaceLandmarker<D> {
+    detector: D,
+    adapter: Option<BoxAdapter>,
+    target_size: Option<f32>,
+    predictor: ShapePredictor
+}
+
+impl<D: Detector> FaceLandmarker<D> {
+    pub fn new(detector: D, predictor: ShapePredictor) -> Self {
+        Self {
+            detector, predictor,
+            adapter: None,
+            target_size: None
+        }
+    }
+
+    /// Correct the boxes from the detector before finding landmarks, for detectors whose boxes
+    /// don't match the ones the shape predictor was trained on.
+    pub fn with_adapter(mut self, adapter: BoxAdapter) -> Self {
+        self.adapter = Some(adapter);
+        self
+    }
+
+    /// Find landmarks on a downscaled copy of faces larger than `target_size` pixels, see `ShapePredictor::run_downscaled`.
+    pub fn with_downscaling(mut self, target_size: f32) -> Self {
+        self.target_size = Some(target_size);
+        self
+    }
+
+    pub fn detector(&mut self) -> &mut D {
+        &mut self.detector
+    }
+
+    pub fn predictor(&self) -> &ShapePredictor {
+        &self.predictor
+    }
+
+    /// Find the landmarks of every face in an image.
+    pub fn run(&mut self, image: &GrayImage) -> Vec<Face> {
+        let adapter = self.adapter;
+        let target_size = self.target_size;
+        let predictor = &self.predictor;
+
+        self.detector.detect(image).into_iter()
+            .map(|(rect, score)| {
+                let rect = match adapter {
+                    Some(adapter) => adapter.apply(&rect),
+                    None => rect
+                };
+
+                let landmarks = match target_size {
+                    Some(target_size) => predictor.run_downscaled(image, &rect, target_size),
+                    None => predictor.run(image, &rect)
+                };
+
+                Face {
+                    landmarks, rect, score
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use landmarker::*;
+    use test_predictors::mean_shape_predictor;
+
+    struct FixedDetector(Vec<(Rectangle, f32)>);
+
+    impl Detector for FixedDetector {
+        fn detect(&mut self, _image: &GrayImage) -> Vec<(Rectangle, f32)> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn adapted_boxes() {
+        let detections = vec![(Rectangle::new(10.0, 20.0, 40.0, 40.0), 2.5), (Rectangle::new(60.0, 10.0, 30.0, 30.0), 1.0)];
+        let adapter = BoxAdapter {
+            x_offset: 0.1,
+            y_offset: -0.05,
+            width_scale: 1.2,
+            height_scale: 1.1,
+            ..BoxAdapter::default()
+        };
+
+        let predictor = mean_shape_predictor(vec![0.25, 0.5, 0.75, 0.5]);
+        let mut landmarker = FaceLandmarker::new(FixedDetector(detections.clone()), predictor).with_adapter(adapter);
+
+        let image = GrayImage::new(100, 80);
+        let faces = landmarker.run(&image);
+        assert_eq!(faces.len(), 2);
+
+        for (face, &(rect, score)) in faces.iter().zip(&detections) {
+            let expected = adapter.apply(&rect);
+
+            assert_eq!(face.rect, expected);
+            assert_eq!(face.score, score);
+            assert_eq!(face.landmarks, landmarker.predictor().run(&image, &expected));
+        }
+    }
+}
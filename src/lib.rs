@@ -1,27 +1,63 @@
 extern crate image;
 extern crate nalgebra;
 extern crate num_traits;
-#[macro_use]
 extern crate nom;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate bincode;
+#[cfg(feature = "visualization")]
+extern crate line_drawing;
+#[cfg(feature = "rustface")]
+extern crate rustface;
 
-use nalgebra::{MatrixVec, Dynamic};
+use nalgebra::{VecStorage, Dynamic};
 pub type Vector2 = nalgebra::Vector2<f32>;
-type Matrix = nalgebra::Matrix<f32, Dynamic, Dynamic, MatrixVec<f32, Dynamic, Dynamic>>;
-
-use image::{GenericImage, Pixel};
-use num_traits::cast::NumCast;
+pub type Vector2d = nalgebra::Vector2<f64>;
+type Matrix = nalgebra::Matrix<f32, Dynamic, Dynamic, VecStorage<f32, Dynamic, Dynamic>>;
 
-mod transformations;
+pub mod transformations;
 mod dlib_parser;
+mod dlib_writer;
 mod serialize;
-use transformations::PointTransformationAffine;
+mod jitter;
+mod rectangle;
+mod intensity;
+mod introspection;
+mod debug;
+mod stages;
+mod ensemble;
+mod hog;
+mod box_adapter;
+mod landmarker;
+mod double_precision;
+mod downscale;
+mod mirror;
+mod shape_model;
+pub mod shapes;
+pub mod metrics;
+pub mod triangulation;
+pub mod warp;
+#[cfg(feature = "visualization")]
+pub mod visualization;
+pub use transformations::{PointTransformationAffine, PointTransformationProjective};
 use serialize::ShapePredictorSerialize;
+pub use jitter::{JitterOptions, JitteredPrediction};
+pub use rectangle::{Rectangle, OrientedRectangle};
+pub use intensity::{IntensitySource, IntensityRange, IntensityValue, LumaBuffer, Normalised};
+pub use debug::CascadeSamples;
+pub use stages::{Stage, Stages};
+use stages::Cascade;
+pub use ensemble::{Ensemble, Combination};
+pub use hog::{HogDetector, Detection};
+pub use box_adapter::BoxAdapter;
+pub use landmarker::{Detector, Face, FaceLandmarker};
+pub use mirror::Symmetry;
+pub use shape_model::ShapeModel;
 
 use std::io::{self, BufReader, BufWriter, Read};
+use std::cmp::Ordering;
+use std::f32;
 use std::fs::File;
 
 #[derive(Debug)]
@@ -29,7 +65,16 @@ pub enum Error {
     ReachedEof(nom::Needed),
     ParsingError(nom::ErrorKind),
     Io(io::Error),
-    Serialization(bincode::Error)
+    Serialization(bincode::Error),
+    /// Shapes, shape predictors or weights that were combined have different numbers of parts.
+    MismatchedParts { expected: usize, found: usize },
+    InvalidEnsemble(&'static str),
+    InvalidDetector(&'static str),
+    InvalidSymmetry(&'static str),
+    /// There was no usable data to fit a model to.
+    NotEnoughSamples,
+    /// The points given to fit a transform don't determine it, e.g. they are collinear, or a transform can't be inverted.
+    DegenerateTransform(&'static str)
 }
 
 impl From<io::Error> for Error {
@@ -44,15 +89,29 @@ impl From<bincode::Error> for Error {
     }
 }
 
-fn length_squared(vec: Vector2) -> f32 {
-    // A^2 + B^2 == C^2
-    vec.x.powi(2) + vec.y.powi(2)
+// The median of the values that aren't NaN, or NaN if they all are.
+fn median(mut values: Vec<f32>) -> f32 {
+    debug_assert!(!values.is_empty());
+
+    values.retain(|value| !value.is_nan());
+
+    if values.is_empty() {
+        return f32::NAN;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    // The two middle values are the same value when there is an odd number of values
+    (values[(values.len() - 1) / 2] + values[values.len() / 2]) / 2.0
 }
 
-fn get_pixel_intensity<I: GenericImage>(image: &I, pos: Vector2) -> f32 {
-    let pixel = image.get_pixel(pos.x as u32, pos.y as u32);
-    let value = pixel.to_luma().data[0];
-    NumCast::from(value).unwrap()
+fn get_pixel_intensity<I: IntensitySource>(image: &I, pos: Vector2) -> f32 {
+    // Pixels outside of the image are treated as black
+    if pos.x >= 0.0 && pos.y >= 0.0 && (pos.x as u32) < image.width() && (pos.y as u32) < image.height() {
+        image.intensity(pos.x as u32, pos.y as u32)
+    } else {
+        0.0
+    }
 }
 
 fn location(shape: &Matrix, idx: u64) -> Vector2 {
@@ -73,74 +132,55 @@ pub struct ShapePredictor {
 impl ShapePredictor {
     // https://github.com/davisking/dlib/blob/master/dlib/image_processing/shape_predictor.h#L339
     /// Run the shape predictor on an image with a specific region of interest and get the positions of landmarks.
-    pub fn run<I: GenericImage>(&self, image: &I, region: &Rectangle) -> Vec<Vector2> {
-        let mut current_shape = self.initial_shape.clone();
-        let mut feature_pixel_values = Vec::new();
-        let tform_to_img = PointTransformationAffine::unnormalising(region);
-
-        for iter in 0 .. self.forests.len() {
-            self.extract_feature_pixel_values(image, region, &current_shape, iter, &mut feature_pixel_values);
-            let mut leaf_idx = 0;
-
-            for tree in &self.forests[iter] {
-                current_shape += tree.find(&feature_pixel_values, &mut leaf_idx);
-            }
-        }
-
-        (0 .. current_shape.len() / 2)
-            .map(|i| tform_to_img.mul(location(&current_shape, i as u64)))
-            .collect()
+    pub fn run<I: IntensitySource>(&self, image: &I, region: &Rectangle) -> Vec<Vector2> {
+        self.run_with_transform(image, &PointTransformationAffine::unnormalising(region))
     }
 
-    fn extract_feature_pixel_values<I: GenericImage>(
-        &self, image: &I, region: &Rectangle, current_shape: &Matrix, iter: usize,
-        feature_pixel_values: &mut Vec<f32>
-    ) {
-        let reference_pixel_anchor_idx = &self.anchor_idx[iter];
-        let reference_pixel_deltas = &self.deltas[iter];
+    /// Run the shape predictor on a rotated region of interest, such as a face with a strong in-plane roll.
+    pub fn run_oriented<I: IntensitySource>(&self, image: &I, region: &OrientedRectangle) -> Vec<Vector2> {
+        self.run_with_transform(image, &PointTransformationAffine::unnormalising_oriented(region))
+    }
 
-        debug_assert_eq!(reference_pixel_anchor_idx.len(), reference_pixel_deltas.len());
-        debug_assert_eq!(current_shape.len(), self.initial_shape.len());
-        debug_assert_eq!(self.initial_shape.len() % 2, 0);
+    /// Run the shape predictor with an arbitrary region of interest, given as a transform that maps
+    /// the unit square onto the region in the image.
+    pub fn run_with_transform<I: IntensitySource>(&self, image: &I, tform_to_img: &PointTransformationAffine) -> Vec<Vector2> {
+        Cascade::new(self, tform_to_img.clone()).run(image)
+    }
 
-        let tform = self.find_tform_between(current_shape).m;
+    /// Run the shape predictor, but stop once the summed update of a cascade's trees is smaller than `threshold`,
+    /// measured as the norm of the update to the whole shape in normalised units where the region of interest is
+    /// the unit square. Returns the landmarks and the number of cascades that were run.
+    pub fn run_until_converged<I: IntensitySource>(&self, image: &I, region: &Rectangle, threshold: f32) -> (Vec<Vector2>, usize) {
         let tform_to_img = PointTransformationAffine::unnormalising(region);
-
-        let area = Rectangle::from_image(image);
-
-        *feature_pixel_values = (0 .. reference_pixel_deltas.len())
-            .map(|i| {
-                let point = tform_to_img.mul(tform * reference_pixel_deltas[i] + location(current_shape, reference_pixel_anchor_idx[i]));
-
-                if area.contains(point) {
-                    get_pixel_intensity(image, point)
-                } else {
-                    0.0
-                }
-            })
-            .collect();
+        let initial_shape = (0 .. self.num_parts()).map(|i| location(&self.initial_shape, i as u64)).collect();
+        self.run_cascade(image, &tform_to_img, initial_shape, Some(threshold))
     }
 
-    fn find_tform_between(&self, to_shape: &Matrix) -> PointTransformationAffine {
-        debug_assert_eq!(self.initial_shape.len(), to_shape.len());
-        debug_assert_eq!(self.initial_shape.len() % 2, 0);
-        debug_assert!(!self.initial_shape.is_empty());
+    /// Run the shape predictor starting from a set of landmarks in image coordinates instead of the mean shape,
+    /// such as the landmarks from a previous frame.
+    pub fn run_from_shape<I: IntensitySource>(&self, image: &I, region: &Rectangle, shape: &[Vector2]) -> Result<Vec<Vector2>, Error> {
+        if shape.len() != self.num_parts() {
+            return Err(Error::MismatchedParts { expected: self.num_parts(), found: shape.len() });
+        }
 
-        let num = self.initial_shape.len() / 2;
+        let initial_shape = PointTransformationAffine::normalising(region).apply(shape);
+        Ok(self.run_cascade(image, &PointTransformationAffine::unnormalising(region), initial_shape, None).0)
+    }
 
-        if num == 1 {
-            PointTransformationAffine::default()
-        } else {
-            let mut from_points = Vec::with_capacity(num);
-            let mut to_points = Vec::with_capacity(num);
+    fn run_cascade<I: IntensitySource>(
+        &self, image: &I, tform_to_img: &PointTransformationAffine, initial_shape: Vec<Vector2>, threshold: Option<f32>
+    ) -> (Vec<Vector2>, usize) {
+        let mut cascade = Cascade::from_shape(self, tform_to_img.clone(), initial_shape);
 
-            for i in 0 .. num as u64 {
-                from_points.push(location(&self.initial_shape, i));
-                to_points.push(location(to_shape, i));
+        while cascade.step(image) {
+            if let Some(threshold) = threshold {
+                if cascade.update < threshold {
+                    break;
+                }
             }
-
-            PointTransformationAffine::find_similarity(&from_points, &to_points)
         }
+
+        (cascade.image_shape(), cascade.index())
     }
 
     /// Serialize the shape predictor to a file.
@@ -164,6 +204,13 @@ impl ShapePredictor {
         File::open(filename)?.read_to_end(&mut buffer)?;
         dlib_parser::parse_shape_predictor(&buffer)
     }
+
+    /// Serialize the shape predictor to a file in the format used by dlib.
+    pub fn write_to_dlib(&self, filename: &str) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        dlib_writer::write_shape_predictor(&mut writer, self)?;
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -187,7 +234,7 @@ impl RegressionTree {
 
         while *i < self.splits.len() {
             let split = &self.splits[*i];
-            if feature_pixel_values[split.idx1] as f32 - feature_pixel_values[split.idx2] as f32 > split.thresh {
+            if feature_pixel_values[split.idx1] - feature_pixel_values[split.idx2] > split.thresh {
                 *i = (2 * *i) + 1;
             } else {
                 *i = (2 * *i) + 2;
@@ -199,55 +246,80 @@ impl RegressionTree {
     }
 }
 
-/// A rectangle in the image.
-#[derive(Debug)]
-pub struct Rectangle {
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32
-}
+#[cfg(test)]
+mod test_predictors {
+    use nalgebra::{Dim, VecStorage};
+    use nalgebra::core::dimension::Dynamic;
+    use {ShapePredictor, RegressionTree, SplitFeature, Matrix, Vector2};
 
-impl Rectangle {
-    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
-        Self {
-            x, y, width, height
-        }
+    pub fn column(values: Vec<f32>) -> Matrix {
+        let data = VecStorage::new(Dynamic::from_usize(values.len()), Dynamic::from_usize(1), values);
+        Matrix::from_data(data)
     }
 
-    pub fn tl_corner(&self) -> Vector2 {
-        Vector2::new(self.x, self.y)
+    /// A shape predictor without any cascades, which always predicts its mean shape.
+    pub fn mean_shape_predictor(initial_shape: Vec<f32>) -> ShapePredictor {
+        ShapePredictor {
+            initial_shape: column(initial_shape),
+            forests: Vec::new(),
+            deltas: Vec::new(),
+            anchor_idx: Vec::new()
+        }
     }
 
-    pub fn tr_corner(&self) -> Vector2 {
-        Vector2::new(self.x + self.width, self.y)
+    fn tree() -> RegressionTree {
+        RegressionTree {
+            splits: vec![SplitFeature { idx1: 0, idx2: 1, thresh: 10.0 }],
+            leaf_values: vec![column(vec![0.05, 0.0, -0.05, 0.1]), column(vec![-0.02, 0.03, 0.0, 0.0])]
+        }
     }
 
-    pub fn bl_corner(&self) -> Vector2 {
-        Vector2::new(self.x, self.y + self.height)
+    /// A shape predictor with 2 parts and 2 cascades of one tree, each comparing a pixel to the right
+    /// of the first part with a pixel to the left of the second part.
+    pub fn two_cascade_predictor() -> ShapePredictor {
+        ShapePredictor {
+            initial_shape: column(vec![0.25, 0.5, 0.75, 0.5]),
+            forests: vec![vec![tree()], vec![tree()]],
+            deltas: vec![vec![Vector2::new(0.1, 0.0), Vector2::new(-0.1, 0.0)]; 2],
+            anchor_idx: vec![vec![0, 1]; 2]
+        }
     }
+}
 
-    pub fn br_corner(&self) -> Vector2 {
-        Vector2::new(self.x + self.width, self.y + self.height)
-    }
+#[test]
+fn run_until_converged() {
+    use image::{GrayImage, Luma};
+
+    let predictor = test_predictors::two_cascade_predictor();
+    let image = GrayImage::from_fn(64, 64, |x, _| Luma([(x * 4) as u8]));
+    let region = Rectangle::new(8.0, 8.0, 48.0, 48.0);
+    let stages: Vec<Stage> = predictor.stages(&image, &region).collect();
+
+    // Both cascades reach the leaf that updates the shape by (-0.02, 0.03), whose norm is 0.036
+    let (shape, count) = predictor.run_until_converged(&image, &region, 0.03);
+    assert_eq!(count, 2);
+    assert_eq!(shape, predictor.run(&image, &region));
+
+    let (shape, count) = predictor.run_until_converged(&image, &region, 0.04);
+    assert_eq!(count, 1);
+    assert_eq!(shape, stages[0].shape);
+}
 
-    fn contains(&self, vec: Vector2) -> bool {
-        vec.x >= self.x && vec.y >= self.y && 
-        vec.x <= (self.x + self.width) && vec.y <= (self.y + self.height)
-    }
+#[test]
+fn run_from_shape() {
+    use image::GrayImage;
 
-    /// Create a rectangle the same size as an image.
-    pub fn from_image<I: GenericImage>(image: &I) -> Self {
-        Self {
-            x: 0.0,
-            y: 0.0,
-            width: image.width() as f32,
-            height: image.height() as f32
-        }
-    }
+    let predictor = test_predictors::mean_shape_predictor(vec![0.25, 0.5, 0.75, 0.5]);
+    let image = GrayImage::new(64, 64);
+    let region = Rectangle::new(8.0, 8.0, 48.0, 48.0);
+    let shape = [Vector2::new(20.0, 30.0), Vector2::new(44.0, 26.0)];
+
+    let result = predictor.run_from_shape(&image, &region, &shape).unwrap();
+    assert!(result.iter().zip(&shape).all(|(a, b)| (a - b).norm() < 1e-4));
+    assert!(predictor.run_from_shape(&image, &region, &shape[.. 1]).is_err());
 }
 
 #[test]
 fn read() {
     ShapePredictor::read("wow.data").unwrap();
-}
\ No newline at end of file
+}
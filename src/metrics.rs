@@ -0,0 +1,159 @@
+//! Ratios computed from the landmarks of the iBUG 68 point markup, for blink, yawn and drowsiness detection.
+
+use Vector2;
+
+fn distance(a: Vector2, b: Vector2) -> f32 {
+    (a - b).norm()
+}
+
+/// The eye aspect ratio (EAR) of Soukupová and Čech for the 6 landmarks of an eye, starting at the outer
+/// or inner corner and going clockwise. It is roughly constant while the eye is open and falls towards 0 as it closes.
+pub fn eye_aspect_ratio(eye: &[Vector2]) -> f32 {
+    debug_assert_eq!(eye.len(), 6);
+
+    (distance(eye[1], eye[5]) + distance(eye[2], eye[4])) / (2.0 * distance(eye[0], eye[3]))
+}
+
+/// The mouth aspect ratio (MAR) for the 8 landmarks of the inner lips, starting at the left corner.
+/// It is close to 0 when the mouth is closed and grows as it opens.
+pub fn mouth_aspect_ratio(inner_lips: &[Vector2]) -> f32 {
+    debug_assert_eq!(inner_lips.len(), 8);
+
+    (distance(inner_lips[1], inner_lips[7]) + distance(inner_lips[2], inner_lips[6]) + distance(inner_lips[3], inner_lips[5])) /
+    (3.0 * distance(inner_lips[0], inner_lips[4]))
+}
+
+/// Metrics of a face from the 68 landmarks found by dlib's face landmark model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FacialMetrics {
+    /// The eye aspect ratio of the subject's left eye (on the right of the image).
+    pub left_eye_aspect_ratio: f32,
+    /// The eye aspect ratio of the subject's right eye (on the left of the image).
+    pub right_eye_aspect_ratio: f32,
+    pub mouth_aspect_ratio: f32,
+    /// The distance between the outer corners of the eyes, in pixels.
+    pub inter_ocular_distance: f32,
+    /// The distance between the ends of the jaw line, in pixels.
+    pub face_width: f32,
+    /// The distance from the bottom of the nose to the chin, relative to the inter-ocular distance.
+    pub jaw_openness: f32
+}
+
+impl FacialMetrics {
+    /// Compute the metrics of a face. Returns `None` if there aren't 68 landmarks.
+    pub fn new(shape: &[Vector2]) -> Option<Self> {
+        if shape.len() != 68 {
+            return None;
+        }
+
+        let inter_ocular_distance = distance(shape[36], shape[45]);
+
+        Some(Self {
+            left_eye_aspect_ratio: eye_aspect_ratio(&shape[42 .. 48]),
+            right_eye_aspect_ratio: eye_aspect_ratio(&shape[36 .. 42]),
+            mouth_aspect_ratio: mouth_aspect_ratio(&shape[60 .. 68]),
+            inter_ocular_distance,
+            face_width: distance(shape[0], shape[16]),
+            jaw_openness: distance(shape[33], shape[8]) / inter_ocular_distance
+        })
+    }
+
+    /// The mean eye aspect ratio of both eyes, which is less noisy than either eye alone.
+    pub fn eye_aspect_ratio(&self) -> f32 {
+        (self.left_eye_aspect_ratio + self.right_eye_aspect_ratio) / 2.0
+    }
+}
+
+/// Counts blinks in a stream of eye aspect ratios, one per video frame.
+///
+/// Eyes are considered closed once the ratio falls below `close_threshold`, and open again once it rises
+/// above `open_threshold`. Having a gap between the thresholds stops noise from counting as several blinks.
+#[derive(Debug, Clone)]
+pub struct BlinkDetector {
+    close_threshold: f32,
+    open_threshold: f32,
+    closed_frames: usize,
+    closed: bool,
+    blinks: usize
+}
+
+impl Default for BlinkDetector {
+    fn default() -> Self {
+        Self::new(0.2, 0.25)
+    }
+}
+
+impl BlinkDetector {
+    pub fn new(close_threshold: f32, open_threshold: f32) -> Self {
+        debug_assert!(close_threshold <= open_threshold);
+
+        Self {
+            close_threshold, open_threshold,
+            closed_frames: 0,
+            closed: false,
+            blinks: 0
+        }
+    }
+
+    /// Update the detector with the eye aspect ratio of the next frame.
+    /// Returns the number of frames that the eyes were closed for if a blink has just finished.
+    pub fn update(&mut self, eye_aspect_ratio: f32) -> Option<usize> {
+        if self.closed {
+            if eye_aspect_ratio > self.open_threshold {
+                self.closed = false;
+                self.blinks += 1;
+                return Some(self.closed_frames);
+            }
+
+            self.closed_frames += 1;
+        } else if eye_aspect_ratio < self.close_threshold {
+            self.closed = true;
+            self.closed_frames = 1;
+        }
+
+        None
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// The number of frames the eyes have been closed for, which can be used to detect drowsiness.
+    pub fn closed_frames(&self) -> usize {
+        if self.closed {
+            self.closed_frames
+        } else {
+            0
+        }
+    }
+
+    /// The number of blinks that have finished.
+    pub fn blinks(&self) -> usize {
+        self.blinks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::*;
+
+    #[test]
+    fn eye_aspect_ratio_of_open_eye() {
+        let eye = [
+            Vector2::new(0.0, 0.0), Vector2::new(1.0, -1.0), Vector2::new(2.0, -1.0),
+            Vector2::new(3.0, 0.0), Vector2::new(2.0, 1.0), Vector2::new(1.0, 1.0)
+        ];
+
+        assert_eq!(eye_aspect_ratio(&eye), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn blink_hysteresis() {
+        let mut detector = BlinkDetector::new(0.2, 0.25);
+        let frames = [0.3, 0.19, 0.22, 0.18, 0.24, 0.3, 0.3, 0.1, 0.3];
+        let blinks: Vec<Option<usize>> = frames.iter().map(|ear| detector.update(*ear)).collect();
+
+        assert_eq!(blinks, [None, None, None, None, None, Some(4), None, None, Some(1)]);
+        assert_eq!(detector.blinks(), 2);
+    }
+}
@@ -0,0 +1,132 @@
+use {ShapePredictor, PointTransformationAffine, IntensitySource, Rectangle, Vector2, Error};
+
+/// The left/right correspondence of the parts of a shape: the part that each part becomes when the face is mirrored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symmetry {
+    mirrored: Vec<usize>
+}
+
+impl Symmetry {
+    /// Create a symmetry from the index of the mirrored part of each part. Parts on the
+    /// center line, such as the tip of the nose, are their own mirror.
+    pub fn new(mirrored: Vec<usize>) -> Result<Self, Error> {
+        for (part, &mirror) in mirrored.iter().enumerate() {
+            if mirror >= mirrored.len() {
+                return Err(Error::InvalidSymmetry("a part is mirrored to a part that doesn't exist"));
+            }
+
+            if mirrored[mirror] != part {
+                return Err(Error::InvalidSymmetry("a part isn't the mirror of its mirror"));
+            }
+        }
+
+        Ok(Self {
+            mirrored
+        })
+    }
+
+    /// Create a symmetry from the pairs of parts that swap places. Other parts are their own mirror.
+    pub fn from_pairs(num_parts: usize, pairs: &[(usize, usize)]) -> Result<Self, Error> {
+        let mut mirrored: Vec<usize> = (0 .. num_parts).collect();
+
+        for &(a, b) in pairs {
+            if a >= num_parts || b >= num_parts {
+                return Err(Error::InvalidSymmetry("a part is mirrored to a part that doesn't exist"));
+            }
+
+            if mirrored[a] != a || mirrored[b] != b {
+                return Err(Error::InvalidSymmetry("a part is in more than one pair"));
+            }
+
+            mirrored[a] = b;
+            mirrored[b] = a;
+        }
+
+        Self::new(mirrored)
+    }
+
+    /// The symmetry of the iBUG 68 point markup used by dlib's face landmark model.
+    pub fn ibug_68() -> Self {
+        let mut pairs: Vec<(usize, usize)> = (0 .. 8).map(|i| (i, 16 - i)).collect();
+
+        pairs.extend_from_slice(&[
+            // Eyebrows
+            (17, 26), (18, 25), (19, 24), (20, 23), (21, 22),
+            // Nose
+            (31, 35), (32, 34),
+            // Eyes
+            (36, 45), (37, 44), (38, 43), (39, 42), (40, 47), (41, 46),
+            // Lips
+            (48, 54), (49, 53), (50, 52), (55, 59), (56, 58),
+            (60, 64), (61, 63), (65, 67)
+        ]);
+
+        Self::from_pairs(68, &pairs).unwrap()
+    }
+
+    pub fn num_parts(&self) -> usize {
+        self.mirrored.len()
+    }
+
+    /// The part that a part becomes when the face is mirrored.
+    pub fn mirror(&self, part: usize) -> usize {
+        self.mirrored[part]
+    }
+}
+
+impl ShapePredictor {
+    /// Run the shape predictor on both the region and its horizontal mirror, and average the landmarks.
+    /// This is twice as slow as `run`, but is usually more accurate and less biased to one side of the face.
+    pub fn run_mirrored<I: IntensitySource>(&self, image: &I, region: &Rectangle, symmetry: &Symmetry) -> Result<Vec<Vector2>, Error> {
+        if symmetry.num_parts() != self.num_parts() {
+            return Err(Error::MismatchedParts { expected: self.num_parts(), found: symmetry.num_parts() });
+        }
+
+        let shape = self.run(image, region);
+
+        // Mapping the left of the unit square to the right of the region runs the cascade on a mirrored face,
+        // without copying the image
+        let tform_to_img = PointTransformationAffine::from_unit_square(region.tr_corner(), region.tl_corner(), region.bl_corner());
+        let mirrored_shape = self.run_with_transform(image, &tform_to_img);
+
+        Ok(shape.iter().enumerate()
+            .map(|(part, point)| (point + mirrored_shape[symmetry.mirror(part)]) / 2.0)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mirror::*;
+    use test_predictors::mean_shape_predictor;
+    use image::GrayImage;
+
+    #[test]
+    fn ibug_68() {
+        let symmetry = Symmetry::ibug_68();
+
+        assert_eq!(symmetry.mirror(0), 16);
+        assert_eq!(symmetry.mirror(45), 36);
+        assert_eq!(symmetry.mirror(30), 30);
+        assert!(Symmetry::new(vec![1, 2, 0]).is_err());
+        assert!(Symmetry::from_pairs(3, &[(0, 1), (1, 2)]).is_err());
+    }
+
+    #[test]
+    fn mirrored_mean_shape() {
+        let predictor = mean_shape_predictor(vec![0.25, 0.4, 0.75, 0.4, 0.5, 0.8]);
+
+        let image = GrayImage::new(100, 100);
+        let region = Rectangle::new(20.0, 10.0, 60.0, 60.0);
+        let symmetry = Symmetry::from_pairs(3, &[(0, 1)]).unwrap();
+
+        let shape = predictor.run(&image, &region);
+        let mirrored = predictor.run_mirrored(&image, &region, &symmetry).unwrap();
+
+        for (a, b) in shape.iter().zip(&mirrored) {
+            assert!((a - b).norm() < 1e-3);
+        }
+
+        assert!(predictor.run_mirrored(&image, &region, &Symmetry::ibug_68()).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use {IntensitySource, Vector2};
+
+/// A rectangle in the image.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rectangle {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32
+}
+
+impl Rectangle {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x, y, width, height
+        }
+    }
+
+    pub fn tl_corner(&self) -> Vector2 {
+        Vector2::new(self.x, self.y)
+    }
+
+    pub fn tr_corner(&self) -> Vector2 {
+        Vector2::new(self.x + self.width, self.y)
+    }
+
+    pub fn bl_corner(&self) -> Vector2 {
+        Vector2::new(self.x, self.y + self.height)
+    }
+
+    pub fn br_corner(&self) -> Vector2 {
+        Vector2::new(self.x + self.width, self.y + self.height)
+    }
+
+    /// Create a rectangle from its top left and bottom right corners.
+    pub fn from_corners(tl: Vector2, br: Vector2) -> Self {
+        Self::new(tl.x, tl.y, br.x - tl.x, br.y - tl.y)
+    }
+
+    /// Create a rectangle of a given size centered on a point.
+    pub fn from_center(center: Vector2, width: f32, height: f32) -> Self {
+        Self::new(center.x - width / 2.0, center.y - height / 2.0, width, height)
+    }
+
+    /// Create a rectangle the same size as an image.
+    pub fn from_image<I: IntensitySource>(image: &I) -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: image.width() as f32,
+            height: image.height() as f32
+        }
+    }
+
+    /// Create the smallest rectangle that contains all of a set of points, such as a set of landmarks.
+    /// Returns `None` if there are no points.
+    pub fn from_points(points: &[Vector2]) -> Option<Self> {
+        let first = points.first()?;
+        let mut tl = *first;
+        let mut br = *first;
+
+        for point in &points[1 ..] {
+            tl.x = tl.x.min(point.x);
+            tl.y = tl.y.min(point.y);
+            br.x = br.x.max(point.x);
+            br.y = br.y.max(point.y);
+        }
+
+        Some(Self::from_corners(tl, br))
+    }
+
+    pub fn center(&self) -> Vector2 {
+        Vector2::new(self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    /// Check if a point lies inside the rectangle, including its edges.
+    pub fn contains(&self, vec: Vector2) -> bool {
+        vec.x >= self.x && vec.y >= self.y &&
+        vec.x <= (self.x + self.width) && vec.y <= (self.y + self.height)
+    }
+
+    /// Get the overlapping area of two rectangles, or `None` if they don't overlap.
+    pub fn intersection(&self, other: &Rectangle) -> Option<Self> {
+        let tl = Vector2::new(self.x.max(other.x), self.y.max(other.y));
+        let br = Vector2::new(
+            (self.x + self.width).min(other.x + other.width),
+            (self.y + self.height).min(other.y + other.height)
+        );
+
+        if tl.x < br.x && tl.y < br.y {
+            Some(Self::from_corners(tl, br))
+        } else {
+            None
+        }
+    }
+
+    /// Get the smallest rectangle that contains both rectangles.
+    pub fn union(&self, other: &Rectangle) -> Self {
+        let tl = Vector2::new(self.x.min(other.x), self.y.min(other.y));
+        let br = Vector2::new(
+            (self.x + self.width).max(other.x + other.width),
+            (self.y + self.height).max(other.y + other.height)
+        );
+
+        Self::from_corners(tl, br)
+    }
+
+    /// Get the intersection over union of two rectangles, a value between 0 (no overlap) and 1 (identical).
+    pub fn iou(&self, other: &Rectangle) -> f32 {
+        let intersection = self.intersection(other).map(|rect| rect.area()).unwrap_or(0.0);
+        let union = self.area() + other.area() - intersection;
+
+        if union > 0.0 {
+            intersection / union
+        } else {
+            0.0
+        }
+    }
+
+    /// Scale the rectangle about its center.
+    pub fn scale(&self, factor: f32) -> Self {
+        Self::from_center(self.center(), self.width * factor, self.height * factor)
+    }
+
+    /// Grow the rectangle by an amount on each side, keeping the same center. A negative amount shrinks it.
+    pub fn pad(&self, amount: f32) -> Self {
+        Self::new(self.x - amount, self.y - amount, self.width + amount * 2.0, self.height + amount * 2.0)
+    }
+
+    /// Clip the rectangle to the bounds of an image with a given width and height.
+    /// If the rectangle lies completely outside the image, the result is empty.
+    pub fn clip(&self, width: u32, height: u32) -> Self {
+        let bounds = Self::new(0.0, 0.0, width as f32, height as f32);
+        self.intersection(&bounds)
+            .unwrap_or_else(|| Self::new(self.x.max(0.0).min(bounds.width), self.y.max(0.0).min(bounds.height), 0.0, 0.0))
+    }
+
+    /// Clip the rectangle to the bounds of an image.
+    pub fn clip_to_image<I: IntensitySource>(&self, image: &I) -> Self {
+        self.clip(image.width(), image.height())
+    }
+}
+
+/// A rectangle in the image that is rotated about its center.
+#[derive(Debug, Clone)]
+pub struct OrientedRectangle {
+    pub center: Vector2,
+    pub width: f32,
+    pub height: f32,
+    /// The clockwise rotation of the rectangle in radians (with the y axis pointing down).
+    pub angle: f32
+}
+
+impl OrientedRectangle {
+    pub fn new(center: Vector2, width: f32, height: f32, angle: f32) -> Self {
+        Self {
+            center, width, height, angle
+        }
+    }
+
+    /// Rotate an axis-aligned rectangle about its center.
+    pub fn from_rectangle(rectangle: &Rectangle, angle: f32) -> Self {
+        let center = Vector2::new(rectangle.x + rectangle.width / 2.0, rectangle.y + rectangle.height / 2.0);
+        Self::new(center, rectangle.width, rectangle.height, angle)
+    }
+
+    /// Rotate an axis-aligned rectangle so that it is level with the line between two eyes,
+    /// for instance the eye positions from a previous frame.
+    pub fn from_eyes(rectangle: &Rectangle, left_eye: Vector2, right_eye: Vector2) -> Self {
+        let (left, right) = if left_eye.x <= right_eye.x {
+            (left_eye, right_eye)
+        } else {
+            (right_eye, left_eye)
+        };
+
+        Self::from_rectangle(rectangle, (right.y - left.y).atan2(right.x - left.x))
+    }
+
+    fn corner(&self, x: f32, y: f32) -> Vector2 {
+        let (sin, cos) = self.angle.sin_cos();
+        let x = (x - 0.5) * self.width;
+        let y = (y - 0.5) * self.height;
+        self.center + Vector2::new(x * cos - y * sin, x * sin + y * cos)
+    }
+
+    pub fn tl_corner(&self) -> Vector2 {
+        self.corner(0.0, 0.0)
+    }
+
+    pub fn tr_corner(&self) -> Vector2 {
+        self.corner(1.0, 0.0)
+    }
+
+    pub fn bl_corner(&self) -> Vector2 {
+        self.corner(0.0, 1.0)
+    }
+
+    pub fn br_corner(&self) -> Vector2 {
+        self.corner(1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rectangle::*;
+
+    #[test]
+    fn intersection_and_union() {
+        let a = Rectangle::new(0.0, 0.0, 10.0, 10.0);
+        let b = Rectangle::new(5.0, 5.0, 10.0, 10.0);
+
+        assert_eq!(a.intersection(&b), Some(Rectangle::new(5.0, 5.0, 5.0, 5.0)));
+        assert_eq!(a.union(&b), Rectangle::new(0.0, 0.0, 15.0, 15.0));
+        assert_eq!(a.iou(&b), 25.0 / 175.0);
+        assert_eq!(a.intersection(&Rectangle::new(20.0, 20.0, 1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn scale_and_clip() {
+        let rect = Rectangle::new(-10.0, 10.0, 20.0, 20.0);
+
+        assert_eq!(rect.scale(2.0), Rectangle::new(-20.0, 0.0, 40.0, 40.0));
+        assert_eq!(rect.pad(5.0), Rectangle::new(-15.0, 5.0, 30.0, 30.0));
+        assert_eq!(rect.clip(100, 25), Rectangle::new(0.0, 10.0, 10.0, 15.0));
+        assert_eq!(Rectangle::from_points(&[Vector2::new(1.0, 4.0), Vector2::new(3.0, 2.0)]), Some(Rectangle::new(1.0, 2.0, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn oriented_rectangle_corners() {
+        let rect = Rectangle::new(10.0, 20.0, 30.0, 40.0);
+        let oriented = OrientedRectangle::from_rectangle(&rect, 0.0);
+        assert!((oriented.tl_corner() - rect.tl_corner()).norm() < 1e-4);
+        assert!((oriented.br_corner() - rect.br_corner()).norm() < 1e-4);
+
+        let rotated = OrientedRectangle::from_rectangle(&rect, ::std::f32::consts::PI);
+        assert!((rotated.tl_corner() - rect.br_corner()).norm() < 1e-4);
+    }
+}
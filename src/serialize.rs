@@ -1,5 +1,5 @@
 use Vector2;
-use nalgebra::{Dim, MatrixVec};
+use nalgebra::{Dim, VecStorage};
 use nalgebra::core::dimension::Dynamic;
 use {ShapePredictor, Matrix, RegressionTree, SplitFeature};
 
@@ -20,7 +20,7 @@ impl MatrixSerialize {
     }
 
     fn to(&self) -> Matrix {
-        let data = MatrixVec::new(Dynamic::from_usize(self.ncols), Dynamic::from_usize(self.nrows), self.vec.clone());
+        let data = VecStorage::new(Dynamic::from_usize(self.ncols), Dynamic::from_usize(self.nrows), self.vec.clone());
         Matrix::from_data(data)
     }
 }
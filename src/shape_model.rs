@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+use nalgebra::{Dynamic, VecStorage};
+use {PointTransformationAffine, Matrix, Vector2, Error};
+use shapes::generalized_procrustes;
+
+/// A statistical shape model, also known as a point distribution model. Shapes are aligned to a mean shape
+/// and their variation is described by a few principal components, or modes. The model can tell how plausible
+/// a shape from the shape predictor is and correct implausible shapes.
+#[derive(Debug, Clone)]
+pub struct ShapeModel {
+    mean: Vec<Vector2>,
+    modes: Vec<Vec<Vector2>>,
+    variances: Vec<f32>
+}
+
+impl ShapeModel {
+    /// Learn a shape model from a set of shapes with the same parts, such as annotated training data.
+    /// Shapes are aligned with generalized Procrustes analysis, and enough modes are kept to explain
+    /// `variance_kept` of the variance, e.g. 0.98.
+    pub fn fit(shapes: &[Vec<Vector2>], variance_kept: f32) -> Result<Self, Error> {
+        if shapes.len() < 2 {
+            return Err(Error::NotEnoughSamples);
+        }
+
+        let procrustes = generalized_procrustes(shapes)?;
+        let (mean, aligned) = (procrustes.mean, procrustes.aligned);
+        let num_parts = mean.len();
+
+        let deviations = aligned.iter()
+            .flat_map(|shape| shape.iter().zip(&mean).flat_map(|(point, mean)| {
+                let deviation = point - mean;
+                vec![deviation.x, deviation.y]
+            }))
+            .collect();
+
+        let deviations = Matrix::from_data(VecStorage::new(Dynamic::new(num_parts * 2), Dynamic::new(shapes.len()), deviations));
+        let covariance = &deviations * deviations.transpose() / (shapes.len() - 1) as f32;
+        let eigen = covariance.symmetric_eigen();
+
+        let mut order: Vec<usize> = (0 .. eigen.eigenvalues.len()).collect();
+        order.sort_by(|&a, &b| eigen.eigenvalues[b].partial_cmp(&eigen.eigenvalues[a]).unwrap_or(Ordering::Equal));
+
+        let total: f32 = eigen.eigenvalues.iter().filter(|&&value| value > 0.0).sum();
+        let mut explained = 0.0;
+        let mut modes = Vec::new();
+        let mut variances = Vec::new();
+
+        for idx in order {
+            let variance = eigen.eigenvalues[idx];
+
+            if variance <= total * 1e-6 || explained >= variance_kept * total {
+                break;
+            }
+
+            let vector = eigen.eigenvectors.column(idx);
+            modes.push((0 .. num_parts).map(|i| Vector2::new(vector[i * 2], vector[i * 2 + 1])).collect());
+            variances.push(variance);
+            explained += variance;
+        }
+
+        Ok(Self {
+            mean, modes, variances
+        })
+    }
+
+    /// The mean shape, centered on the origin and scaled to unit norm.
+    pub fn mean(&self) -> &[Vector2] {
+        &self.mean
+    }
+
+    pub fn num_parts(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn num_modes(&self) -> usize {
+        self.modes.len()
+    }
+
+    /// The variance of the shapes along each mode, from the largest.
+    pub fn variances(&self) -> &[f32] {
+        &self.variances
+    }
+
+    // The transform that aligns a shape to the mean shape.
+    fn alignment(&self, shape: &[Vector2]) -> Result<PointTransformationAffine, Error> {
+        if shape.len() != self.num_parts() {
+            return Err(Error::MismatchedParts { expected: self.num_parts(), found: shape.len() });
+        }
+
+        Ok(PointTransformationAffine::find_similarity(shape, &self.mean))
+    }
+
+    fn parameters(&self, aligned: &[Vector2]) -> Vec<f32> {
+        self.modes.iter()
+            .map(|mode| mode.iter().zip(aligned).zip(&self.mean).map(|((mode, point), mean)| mode.dot(&(point - mean))).sum())
+            .collect()
+    }
+
+    /// Project a shape into the model, getting its position along each mode after it has been aligned to the mean shape.
+    pub fn project(&self, shape: &[Vector2]) -> Result<Vec<f32>, Error> {
+        Ok(self.parameters(&self.alignment(shape)?.apply(shape)))
+    }
+
+    /// Build a shape, aligned to the mean shape, from its position along each mode.
+    pub fn reconstruct(&self, parameters: &[f32]) -> Vec<Vector2> {
+        debug_assert_eq!(parameters.len(), self.num_modes());
+
+        let mut shape = self.mean.clone();
+
+        for (mode, &parameter) in self.modes.iter().zip(parameters) {
+            for (point, offset) in shape.iter_mut().zip(mode) {
+                *point += offset * parameter;
+            }
+        }
+
+        shape
+    }
+
+    /// How far a shape is from the mean shape, in standard deviations (the Mahalanobis distance in the model).
+    /// Shapes from the shape predictor that are much further than 3 are likely to be wrong.
+    pub fn mahalanobis_distance(&self, shape: &[Vector2]) -> Result<f32, Error> {
+        let distance = self.project(shape)?.iter().zip(&self.variances)
+            .map(|(parameter, variance)| parameter.powi(2) / variance)
+            .sum::<f32>()
+            .sqrt();
+
+        Ok(distance)
+    }
+
+    /// Correct a shape by limiting its position along each mode to `k` standard deviations from the mean shape,
+    /// keeping its position, rotation and scale in the image.
+    pub fn clamp(&self, shape: &[Vector2], k: f32) -> Result<Vec<Vector2>, Error> {
+        let alignment = self.alignment(shape)?;
+
+        let parameters: Vec<f32> = self.parameters(&alignment.apply(shape)).iter().zip(&self.variances)
+            .map(|(&parameter, &variance)| {
+                let limit = k * variance.sqrt();
+                parameter.max(-limit).min(limit)
+            })
+            .collect();
+
+        match alignment.inverse() {
+            Ok(inverse) => Ok(inverse.apply(&self.reconstruct(&parameters))),
+            Err(_) => Ok(shape.to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shape_model::*;
+    use nalgebra::Matrix2;
+
+    // A square stretched by `t`, rotated, scaled and moved.
+    fn shape(t: f32, angle: f32, scale: f32, offset: f32) -> Vec<Vector2> {
+        let rotation = Matrix2::new(angle.cos(), -angle.sin(), angle.sin(), angle.cos()) * scale;
+
+        [(-1.0 - t, -1.0), (1.0 + t, -1.0), (1.0 + t, 1.0), (-1.0 - t, 1.0), (0.0, 0.0)].iter()
+            .map(|&(x, y)| rotation * Vector2::new(x, y) + Vector2::new(offset, offset * 2.0))
+            .collect()
+    }
+
+    #[test]
+    fn stretched_squares() {
+        let shapes: Vec<Vec<Vector2>> = (0 .. 10)
+            .map(|i| shape((i as f32 - 4.5) * 0.04, i as f32 * 0.3, 1.0 + i as f32 * 0.2, i as f32 * 5.0))
+            .collect();
+
+        let model = ShapeModel::fit(&shapes, 0.98).unwrap();
+        assert_eq!(model.num_modes(), 1);
+
+        let plausible = shape(0.1, 1.0, 3.0, 20.0);
+        let implausible = shape(1.5, 1.0, 3.0, 20.0);
+
+        assert!(model.mahalanobis_distance(&plausible).unwrap() < 3.0);
+        assert!(model.mahalanobis_distance(&implausible).unwrap() > 5.0);
+
+        let clamped = model.clamp(&implausible, 3.0).unwrap();
+        assert!(model.mahalanobis_distance(&clamped).unwrap() < 3.01);
+        assert!((clamped[4] - implausible[4]).norm() < 1e-3);
+
+        assert!(model.project(&plausible[.. 4]).is_err());
+        assert!(model.mahalanobis_distance(&plausible[.. 4]).is_err());
+        assert!(model.clamp(&plausible[.. 4], 3.0).is_err());
+
+        assert!(ShapeModel::fit(&shapes[.. 1], 0.98).is_err());
+    }
+}
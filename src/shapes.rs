@@ -0,0 +1,164 @@
+//! Operations on whole shapes: Procrustes alignment, mean shapes and conversion between image coordinates
+//! and the coordinates normalised to a region of interest that shape predictors work in.
+
+use {PointTransformationAffine, Rectangle, Vector2, Error};
+
+fn check_shapes<S: AsRef<[Vector2]>>(shapes: &[S]) -> Result<usize, Error> {
+    let num_parts = match shapes.first() {
+        Some(shape) if !shape.as_ref().is_empty() => shape.as_ref().len(),
+        _ => return Err(Error::NotEnoughSamples)
+    };
+
+    match shapes.iter().find(|shape| shape.as_ref().len() != num_parts) {
+        Some(shape) => Err(Error::MismatchedParts { expected: num_parts, found: shape.as_ref().len() }),
+        None => Ok(num_parts)
+    }
+}
+
+fn mean<S: AsRef<[Vector2]>>(shapes: &[S], num_parts: usize) -> Vec<Vector2> {
+    let mut mean = vec![Vector2::zeros(); num_parts];
+
+    for shape in shapes {
+        for (sum, point) in mean.iter_mut().zip(shape.as_ref()) {
+            *sum += point;
+        }
+    }
+
+    for point in &mut mean {
+        *point /= shapes.len() as f32;
+    }
+
+    mean
+}
+
+/// The centroid of a shape.
+pub fn centroid(shape: &[Vector2]) -> Vector2 {
+    shape.iter().fold(Vector2::zeros(), |sum, point| sum + point) / shape.len() as f32
+}
+
+/// Move a shape so that its centroid is at the origin and scale it to unit norm.
+pub fn normalise(shape: &[Vector2]) -> Vec<Vector2> {
+    let centroid = centroid(shape);
+    let norm = shape.iter().map(|point| (point - centroid).norm_squared()).sum::<f32>().sqrt();
+    let norm = if norm > 0.0 { norm } else { 1.0 };
+
+    shape.iter().map(|point| (point - centroid) / norm).collect()
+}
+
+/// Align a shape to a reference shape with the least squares similarity transform, returning the
+/// transform and the aligned shape.
+pub fn align(shape: &[Vector2], reference: &[Vector2]) -> Result<(PointTransformationAffine, Vec<Vector2>), Error> {
+    if shape.len() != reference.len() {
+        return Err(Error::MismatchedParts { expected: reference.len(), found: shape.len() });
+    }
+
+    let tform = PointTransformationAffine::find_similarity(shape, reference);
+    let aligned = tform.apply(shape);
+
+    Ok((tform, aligned))
+}
+
+/// The result of generalized Procrustes analysis.
+#[derive(Debug, Clone)]
+pub struct Procrustes {
+    /// The mean shape, centered on the origin and scaled to unit norm.
+    pub mean: Vec<Vector2>,
+    /// Each shape after it has been aligned to the mean shape.
+    pub aligned: Vec<Vec<Vector2>>,
+    /// The transform that aligns each shape to the mean shape.
+    pub transforms: Vec<PointTransformationAffine>
+}
+
+/// Align a set of shapes to their mean with similarity transforms until the mean stops changing.
+/// This removes differences in position, rotation and scale, leaving only differences in shape.
+pub fn generalized_procrustes<S: AsRef<[Vector2]>>(shapes: &[S]) -> Result<Procrustes, Error> {
+    let num_parts = check_shapes(shapes)?;
+    let mut procrustes = Procrustes {
+        mean: normalise(shapes[0].as_ref()),
+        aligned: Vec::new(),
+        transforms: Vec::new()
+    };
+
+    for _ in 0 .. 100 {
+        let (transforms, aligned) = shapes.iter()
+            .map(|shape| align(shape.as_ref(), &procrustes.mean))
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .unzip();
+        procrustes.transforms = transforms;
+        procrustes.aligned = aligned;
+
+        // Keep the orientation of the mean fixed, otherwise it can drift
+        let new_mean = normalise(&mean(&procrustes.aligned, num_parts));
+        let new_mean = normalise(&align(&new_mean, &procrustes.mean)?.1);
+
+        let change: f32 = procrustes.mean.iter().zip(&new_mean).map(|(a, b)| (a - b).norm_squared()).sum();
+        procrustes.mean = new_mean;
+
+        if change < 1e-12 {
+            break;
+        }
+    }
+
+    Ok(procrustes)
+}
+
+/// Convert a shape in image coordinates to coordinates normalised to a region of interest,
+/// where the region is the unit square.
+pub fn to_normalised(shape: &[Vector2], region: &Rectangle) -> Vec<Vector2> {
+    PointTransformationAffine::normalising(region).apply(shape)
+}
+
+/// Convert a shape in coordinates normalised to a region of interest back to image coordinates.
+pub fn from_normalised(shape: &[Vector2], region: &Rectangle) -> Vec<Vector2> {
+    PointTransformationAffine::unnormalising(region).apply(shape)
+}
+
+/// The mean of a set of shapes after normalising each to its region of interest, such as the box from
+/// a face detector. This is how dlib computes the initial shape of a shape predictor when training it.
+pub fn mean_shape<S: AsRef<[Vector2]>>(shapes: &[(S, Rectangle)]) -> Result<Vec<Vector2>, Error> {
+    let normalised: Vec<Vec<Vector2>> = shapes.iter()
+        .map(|(shape, region)| to_normalised(shape.as_ref(), region))
+        .collect();
+
+    let num_parts = check_shapes(&normalised)?;
+    Ok(mean(&normalised, num_parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use shapes::*;
+    use nalgebra::Matrix2;
+
+    #[test]
+    fn procrustes() {
+        let square = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 1.0), Vector2::new(0.0, 1.0)];
+        let rotation = PointTransformationAffine::new(Matrix2::new(0.0, -2.0, 2.0, 0.0), Vector2::new(5.0, 3.0));
+        let shapes = vec![square.to_vec(), rotation.apply(&square)];
+
+        let procrustes = generalized_procrustes(&shapes).unwrap();
+
+        for point in &procrustes.aligned[1] {
+            assert!((point.norm() - 0.5).abs() < 1e-4);
+        }
+
+        assert!(procrustes.aligned[0].iter().zip(&procrustes.aligned[1]).all(|(a, b)| (a - b).norm() < 1e-4));
+        assert!(generalized_procrustes::<Vec<Vector2>>(&[]).is_err());
+        assert!(align(&square[.. 3], &square).is_err());
+    }
+
+    #[test]
+    fn normalised_mean_shape() {
+        let region = Rectangle::new(10.0, 20.0, 100.0, 50.0);
+        let shape = [Vector2::new(35.0, 45.0), Vector2::new(85.0, 45.0)];
+
+        let normalised = to_normalised(&shape, &region);
+        assert!((normalised[0] - Vector2::new(0.25, 0.5)).norm() < 1e-5);
+        assert!((from_normalised(&normalised, &region)[1] - shape[1]).norm() < 1e-4);
+
+        let other = Rectangle::new(0.0, 0.0, 10.0, 10.0);
+        let mean = mean_shape(&[(shape.to_vec(), region), (vec![Vector2::new(3.5, 5.0), Vector2::new(7.5, 5.0)], other)]).unwrap();
+        assert!((mean[0] - Vector2::new(0.3, 0.5)).norm() < 1e-5);
+        assert!((mean[1] - Vector2::new(0.75, 0.5)).norm() < 1e-5);
+    }
+}
@@ -0,0 +1,210 @@
+use nalgebra::{self, Real};
+use {ShapePredictor, PointTransformationAffine, IntensitySource, Rectangle, Vector2, get_pixel_intensity, location};
+
+type Point<N> = nalgebra::Vector2<N>;
+
+fn convert_point<N: Real>(point: Vector2) -> Point<N> {
+    point.map(|value| nalgebra::convert(f64::from(value)))
+}
+
+fn mean_shape<N: Real>(predictor: &ShapePredictor) -> Vec<Point<N>> {
+    (0 .. predictor.num_parts())
+        .map(|i| convert_point(location(&predictor.initial_shape, i as u64)))
+        .collect()
+}
+
+// Pixels are sampled at single precision positions, whatever the precision of the shape.
+fn to_f32<N: Real>(point: Point<N>) -> Vector2 {
+    point.map(|value| nalgebra::try_convert::<N, f64>(value).unwrap_or(0.0) as f32)
+}
+
+/// A shape predictor part way through its cascades. Every way of running the predictor steps through
+/// the cascades with this, in single or double precision.
+pub struct Cascade<'a, N: Real> {
+    predictor: &'a ShapePredictor,
+    tform_to_img: PointTransformationAffine<N>,
+    mean_shape: Vec<Point<N>>,
+    /// The landmarks in normalised coordinates, where the region of interest is the unit square.
+    pub shape: Vec<Point<N>>,
+    /// The similarity transform from the mean shape to the shape at the start of the last cascade.
+    pub transform: PointTransformationAffine<N>,
+    /// The position in the image of each pixel sampled by the last cascade.
+    pub positions: Vec<Vector2>,
+    /// The intensity of each pixel sampled by the last cascade.
+    pub intensities: Vec<f32>,
+    /// The index of the leaf reached in each regression tree of the last cascade.
+    pub leaves: Vec<usize>,
+    /// The norm of the summed update of the last cascade's trees, in normalised units.
+    pub update: N,
+    iter: usize
+}
+
+impl<'a, N: Real> Cascade<'a, N> {
+    /// Start from the mean shape of the predictor.
+    pub fn new(predictor: &'a ShapePredictor, tform_to_img: PointTransformationAffine<N>) -> Self {
+        Self::from_shape(predictor, tform_to_img, mean_shape(predictor))
+    }
+
+    /// Start from a shape in normalised coordinates.
+    pub fn from_shape(predictor: &'a ShapePredictor, tform_to_img: PointTransformationAffine<N>, shape: Vec<Point<N>>) -> Self {
+        debug_assert_eq!(shape.len(), predictor.num_parts());
+
+        Self {
+            predictor, tform_to_img, shape,
+            mean_shape: mean_shape(predictor),
+            transform: PointTransformationAffine::default(),
+            positions: Vec::new(),
+            intensities: Vec::new(),
+            leaves: Vec::new(),
+            update: N::zero(),
+            iter: 0
+        }
+    }
+
+    /// The number of cascades that have been run.
+    pub fn index(&self) -> usize {
+        self.iter
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.predictor.forests.len() - self.iter
+    }
+
+    /// Run the next cascade. Returns false, without changing anything, once every cascade has been run.
+    pub fn step<I: IntensitySource>(&mut self, image: &I) -> bool {
+        let predictor = self.predictor;
+        let iter = self.iter;
+
+        if iter >= predictor.forests.len() {
+            return false;
+        }
+
+        let reference_pixel_anchor_idx = &predictor.anchor_idx[iter];
+        let reference_pixel_deltas = &predictor.deltas[iter];
+        debug_assert_eq!(reference_pixel_anchor_idx.len(), reference_pixel_deltas.len());
+
+        self.transform = if self.shape.len() == 1 {
+            PointTransformationAffine::default()
+        } else {
+            PointTransformationAffine::find_similarity(&self.mean_shape, &self.shape)
+        };
+
+        let (tform, tform_to_img, shape) = (self.transform.m, &self.tform_to_img, &self.shape);
+
+        self.positions.clear();
+        self.positions.extend(reference_pixel_anchor_idx.iter().zip(reference_pixel_deltas)
+            .map(|(&anchor, &delta)| to_f32(tform_to_img.mul(tform * convert_point(delta) + shape[anchor as usize]))));
+
+        self.intensities.clear();
+        self.intensities.extend(self.positions.iter().map(|&point| get_pixel_intensity(image, point)));
+
+        let mut update = vec![Point::zeros(); self.shape.len()];
+        let mut leaf_idx = 0;
+        self.leaves.clear();
+
+        for tree in &predictor.forests[iter] {
+            let leaf = tree.find(&self.intensities, &mut leaf_idx);
+
+            for (i, (point, sum)) in self.shape.iter_mut().zip(&mut update).enumerate() {
+                let offset = convert_point(location(leaf, i as u64));
+                *point += offset;
+                *sum += offset;
+            }
+
+            self.leaves.push(leaf_idx);
+        }
+
+        self.update = update.iter().fold(N::zero(), |total, offset| total + offset.norm_squared()).sqrt();
+        self.iter += 1;
+
+        true
+    }
+
+    /// The landmarks in image coordinates.
+    pub fn image_shape(&self) -> Vec<Point<N>> {
+        self.tform_to_img.apply(&self.shape)
+    }
+
+    /// Run the remaining cascades and get the landmarks in image coordinates.
+    pub fn run<I: IntensitySource>(mut self, image: &I) -> Vec<Point<N>> {
+        while self.step(image) {}
+        self.image_shape()
+    }
+}
+
+/// The state of the shape predictor after one cascade.
+#[derive(Debug, Clone)]
+pub struct Stage {
+    /// The index of the cascade, starting at 0.
+    pub index: usize,
+    /// The landmarks after the cascade has been applied, in image coordinates.
+    pub shape: Vec<Vector2>,
+    /// The index of the leaf reached in each regression tree of the cascade.
+    pub leaves: Vec<usize>,
+    /// The similarity transform from the mean shape to the shape at the start of the cascade,
+    /// in normalised coordinates. This is the transform that the sampled pixel offsets are rotated
+    /// and scaled by.
+    pub transform: PointTransformationAffine
+}
+
+/// An iterator over the cascades of a shape predictor, created by `ShapePredictor::stages`.
+pub struct Stages<'a, I: 'a> {
+    image: &'a I,
+    cascade: Cascade<'a, f32>
+}
+
+impl<'a, I: IntensitySource> Iterator for Stages<'a, I> {
+    type Item = Stage;
+
+    fn next(&mut self) -> Option<Stage> {
+        let index = self.cascade.index();
+
+        if !self.cascade.step(self.image) {
+            return None;
+        }
+
+        Some(Stage {
+            index,
+            shape: self.cascade.image_shape(),
+            leaves: self.cascade.leaves.clone(),
+            transform: self.cascade.transform.clone()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.cascade.remaining();
+        (remaining, Some(remaining))
+    }
+}
+
+impl ShapePredictor {
+    /// Run the shape predictor one cascade at a time. The shape of the last stage is the same as the result of `run`.
+    pub fn stages<'a, I: IntensitySource>(&'a self, image: &'a I, region: &Rectangle) -> Stages<'a, I> {
+        Stages {
+            image,
+            cascade: Cascade::new(self, PointTransformationAffine::unnormalising(region))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stages::*;
+    use test_predictors::two_cascade_predictor;
+    use image::{GrayImage, Luma};
+
+    #[test]
+    fn last_stage_matches_run() {
+        let predictor = two_cascade_predictor();
+        let image = GrayImage::from_fn(64, 64, |x, _| Luma([(x * 4) as u8]));
+        let region = Rectangle::new(8.0, 8.0, 48.0, 48.0);
+
+        let stages: Vec<Stage> = predictor.stages(&image, &region).collect();
+
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[1].index, 1);
+        assert_eq!(stages[1].leaves.len(), 1);
+        assert_eq!(stages[1].shape, predictor.run(&image, &region));
+        assert_eq!(predictor.sample_cascades(&image, &region)[1].shape, stages[1].shape);
+    }
+}
@@ -1,50 +1,124 @@
-use nalgebra::{Matrix, Matrix2, Matrix2x3, Dim, DimName, Dynamic, MatrixVec};
-use nalgebra::storage::Storage;
-use {length_squared, Rectangle, Vector2};
+//! Affine and projective transforms of points, and least squares fits of them to pairs of point sets.
 
-type ColVectorN<N> = Matrix<f32, N, Dynamic, MatrixVec<f32, N, Dynamic>>;
+use nalgebra::{self, Matrix, Matrix2, Matrix2x3, Matrix3, Dim, DimName, Dynamic, VecStorage, Real};
+use {Rectangle, OrientedRectangle, Error};
 
-fn new_col_vector<N: Dim + DimName>(width: usize, height: usize) -> ColVectorN<N> {
-    let data = MatrixVec::new(N::from_usize(width), Dynamic::new(height), vec![0.0; width * height]);
+type Point<N> = nalgebra::Vector2<N>;
+type ColVectorN<N, D> = Matrix<N, D, Dynamic, VecStorage<N, D, Dynamic>>;
+
+type DynamicMatrix<N> = Matrix<N, Dynamic, Dynamic, VecStorage<N, Dynamic, Dynamic>>;
+
+fn new_col_vector<N: Real, D: Dim + DimName>(width: usize, height: usize) -> ColVectorN<N, D> {
+    let data = VecStorage::new(D::from_usize(width), Dynamic::new(height), vec![N::zero(); width * height]);
     ColVectorN::from_data(data)
 }
 
-pub struct PointTransformationAffine {
-    pub m: Matrix2<f32>,
-    b: Vector2
+fn new_matrix<N: Real>(rows: usize, cols: usize) -> DynamicMatrix<N> {
+    let data = VecStorage::new(Dynamic::new(rows), Dynamic::new(cols), vec![N::zero(); rows * cols]);
+    DynamicMatrix::from_data(data)
 }
 
-impl PointTransformationAffine {
-    pub fn new(m: Matrix2<f32>, b: Vector2) -> Self {
+fn check_lengths<N: Real>(from_points: &[Point<N>], to_points: &[Point<N>], min: usize) -> Result<(), Error> {
+    if from_points.len() != to_points.len() {
+        return Err(Error::MismatchedParts { expected: from_points.len(), found: to_points.len() });
+    }
+
+    if from_points.len() < min {
+        return Err(Error::DegenerateTransform("not enough points to fit the transform"));
+    }
+
+    Ok(())
+}
+
+// Whether the points all lie on a line (or on a single point), relative to their spread.
+fn collinear<N: Real>(points: &[Point<N>]) -> bool {
+    let count: N = nalgebra::convert(points.len() as f64);
+    let mean = points.iter().fold(Point::zeros(), |sum, point| sum + point) / count;
+
+    let cov = points.iter().fold(Matrix2::zeros(), |cov: Matrix2<N>, point| {
+        let centered = point - mean;
+        cov + centered * centered.transpose()
+    });
+
+    let tolerance: N = nalgebra::convert(1e-6);
+    cov.trace() == N::zero() || cov.determinant() <= tolerance * cov.trace() * cov.trace()
+}
+
+// Infinities and NaNs are the only values that aren't within the bounds of the type.
+fn is_finite<N: Real>(value: N) -> bool {
+    value.abs() <= N::max_value()
+}
+
+// The top left, top right and bottom right corners of the normalised region of interest
+fn unit_square<N: Real>() -> [Point<N>; 3] {
+    [
+        Point::new(N::zero(), N::zero()),
+        Point::new(N::one(), N::zero()),
+        Point::new(N::one(), N::one())
+    ]
+}
+
+/// An affine transform of points, in single precision by default.
+/// Transforms in double precision can be used for very large images or for shapes that are tracked over many frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointTransformationAffine<N: Real = f32> {
+    pub m: Matrix2<N>,
+    b: Point<N>
+}
+
+impl<N: Real> Default for PointTransformationAffine<N> {
+    fn default() -> Self {
         Self {
-            m, b
+            m: Matrix2::identity(),
+            b: Point::zeros()
         }
     }
+}
 
-    pub fn default() -> Self {
+impl<N: Real> PointTransformationAffine<N> {
+    pub fn new(m: Matrix2<N>, b: Point<N>) -> Self {
         Self {
-            m: Matrix2::identity(),
-            b: Vector2::new(0.0, 0.0)
+            m, b
         }
     }
 
-    pub fn mul(&self, p: Vector2) -> Vector2 {
+    pub fn mul(&self, p: Point<N>) -> Point<N> {
         self.m * p + self.b
     }
 
-    pub fn unnormalising(rectangle: &Rectangle) -> Self {
-        Self::find_affine(&[
-            Vector2::new(0.0, 0.0),
-            Vector2::new(1.0, 0.0),
-            Vector2::new(1.0, 1.0)
-        ], &[
-            rectangle.tl_corner(),
-            rectangle.tr_corner(),
-            rectangle.br_corner()
-        ])
-    }
-
-    pub fn find_affine(from_points: &[Vector2], to_points: &[Vector2]) -> Self {
+    pub fn translation(&self) -> Point<N> {
+        self.b
+    }
+
+    /// Transform every point of a shape.
+    pub fn apply(&self, points: &[Point<N>]) -> Vec<Point<N>> {
+        points.iter().map(|point| self.mul(*point)).collect()
+    }
+
+    /// The transform that applies `other` first and then this transform.
+    pub fn compose(&self, other: &Self) -> Self {
+        Self::new(self.m * other.m, self.m * other.b + self.b)
+    }
+
+    /// The transform that undoes this transform. Fails if the transform squashes the plane onto a line or a point.
+    pub fn inverse(&self) -> Result<Self, Error> {
+        match self.m.try_inverse() {
+            Some(m) if m.iter().all(|value| is_finite(*value)) => Ok(Self::new(m, -(m * self.b))),
+            _ => Err(Error::DegenerateTransform("the transform is singular"))
+        }
+    }
+
+    /// The transform that maps the unit square onto the square or parallelogram with these corners.
+    pub fn from_unit_square(tl: Point<N>, tr: Point<N>, br: Point<N>) -> Self {
+        Self::find_affine(&unit_square(), &[tl, tr, br])
+    }
+
+    /// The transform that maps the square or parallelogram with these corners onto the unit square.
+    pub fn to_unit_square(tl: Point<N>, tr: Point<N>, br: Point<N>) -> Self {
+        Self::find_affine(&[tl, tr, br], &unit_square())
+    }
+
+    pub fn find_affine(from_points: &[Point<N>], to_points: &[Point<N>]) -> Self {
         debug_assert_eq!(from_points.len(), to_points.len());
         debug_assert!(from_points.len() >= 3);
 
@@ -54,86 +128,319 @@ impl PointTransformationAffine {
         for i in 0 .. from_points.len() {
             p[(0, i)] = from_points[i].x;
             p[(1, i)] = from_points[i].y;
-            p[(2, i)] = 1.0;
+            p[(2, i)] = N::one();
 
             q[(0, i)] = to_points[i].x;
             q[(1, i)] = to_points[i].y;
         }
 
-        let m: Matrix2x3<f32> = q * p.pseudo_inverse(0.0);
+        let m: Matrix2x3<N> = q * p.pseudo_inverse(N::zero());
 
-        let slice = m.slice((0, 0), (2, 2));
-        let data = slice.data.as_slice();
-        let mat = Matrix2::new(data[0], data[1], data[2], data[3]);
+        let mat = Matrix2::new(m[(0, 0)], m[(0, 1)], m[(1, 0)], m[(1, 1)]);
 
         let col = m.column(2).into_owned();
 
         Self::new(mat, col)
     }
 
-    pub fn find_similarity(from_points: &[Vector2], to_points: &[Vector2]) -> Self {
+    pub fn find_similarity(from_points: &[Point<N>], to_points: &[Point<N>]) -> Self {
+        Self::umeyama(from_points, to_points, true)
+    }
+
+    /// Find the least squares rotation and translation, without scaling, from one set of points to another.
+    pub fn fit_rigid(from_points: &[Point<N>], to_points: &[Point<N>]) -> Result<Self, Error> {
+        check_lengths(from_points, to_points, 2)?;
+
+        if from_points.iter().all(|point| *point == from_points[0]) {
+            return Err(Error::DegenerateTransform("the points are all the same"));
+        }
+
+        Ok(Self::umeyama(from_points, to_points, false))
+    }
+
+    /// Like `find_similarity`, but fails for point sets that don't determine a unique transform.
+    pub fn fit_similarity(from_points: &[Point<N>], to_points: &[Point<N>]) -> Result<Self, Error> {
+        check_lengths(from_points, to_points, 2)?;
+
+        if from_points.iter().all(|point| *point == from_points[0]) {
+            return Err(Error::DegenerateTransform("the points are all the same"));
+        }
+
+        Ok(Self::umeyama(from_points, to_points, true))
+    }
+
+    /// Like `find_affine`, but fails for point sets that don't determine a unique transform.
+    pub fn fit_affine(from_points: &[Point<N>], to_points: &[Point<N>]) -> Result<Self, Error> {
+        check_lengths(from_points, to_points, 3)?;
+
+        if collinear(from_points) {
+            return Err(Error::DegenerateTransform("the points are collinear"));
+        }
+
+        Ok(Self::find_affine(from_points, to_points))
+    }
+
+    // http://web.stanford.edu/class/cs273/refs/umeyama.pdf
+    fn umeyama(from_points: &[Point<N>], to_points: &[Point<N>], with_scale: bool) -> Self {
         debug_assert_eq!(from_points.len(), to_points.len());
 
-        let mut mean_to = Vector2::new(0.0, 0.0);
-        let mut mean_from = Vector2::new(0.0, 0.0);
-        let mut sigma_from = 0.0;
+        let count: N = nalgebra::convert(from_points.len() as f64);
+
+        let mut mean_to = Point::zeros();
+        let mut mean_from = Point::zeros();
+        let mut sigma_from = N::zero();
 
-        let mut cov = Matrix2::new(0.0, 0.0, 0.0, 0.0);
+        let mut cov = Matrix2::zeros();
 
         for i in 0 .. from_points.len() {
             mean_from += from_points[i];
             mean_to += to_points[i];
         }
 
-        mean_from /= from_points.len() as f32;
-        mean_to /= to_points.len() as f32;
+        mean_from /= count;
+        mean_to /= count;
 
         for i in 0 .. from_points.len() {
-            sigma_from += length_squared(from_points[i] - mean_from);
+            sigma_from += (from_points[i] - mean_from).norm_squared();
             cov += (to_points[i] - mean_to) * (from_points[i] - mean_from).transpose();
         }
 
-        sigma_from /= from_points.len() as f32;
-        cov /= from_points.len() as f32;
+        sigma_from /= count;
+        cov /= count;
 
-        let d = diagm(cov);
         let svd = cov.svd(true, true);
 
+        let d = Matrix2::from_diagonal(&svd.singular_values);
         let u = svd.u.unwrap();
-        let v = svd.v_t.unwrap();
+        let v_t = svd.v_t.unwrap();
 
         let mut s = Matrix2::identity();
 
-        if cov.determinant() < 0.0 || (cov.determinant() == 0.0 && u.determinant() * v.determinant() < 0.0) {
+        if cov.determinant() < N::zero() || (cov.determinant() == N::zero() && u.determinant() * v_t.determinant() < N::zero()) {
             if d[(1,1)] < d[(0,0)] {
-                s[(1,1)] = -1.0;
+                s[(1,1)] = -N::one();
             } else {
-                s[(0,0)] = -1.0;
+                s[(0,0)] = -N::one();
             }
         }
 
-        let r = u * s * v.transpose();
+        let r = u * s * v_t;
 
-        let c = if sigma_from == 0.0 {
-            1.0
+        let c = if !with_scale || sigma_from == N::zero() {
+            N::one()
         } else {
-            1.0 / sigma_from * (d * s).trace()
+            N::one() / sigma_from * (d * s).trace()
         };
 
-        let t = mean_to - c * r * mean_from;
+        let t = mean_to - r * c * mean_from;
 
-        Self::new(c * r, t)
+        Self::new(r * c, t)
     }
 }
 
-fn diagm(mut mat: Matrix2<f32>) -> Matrix2<f32> {
-    for r in 0 .. mat.nrows() {
-        for c in 0 .. mat.ncols() {
-            if r != c {
-                mat[(c, r)] = 0.0;
-            }
+impl PointTransformationAffine {
+    pub fn unnormalising(rectangle: &Rectangle) -> Self {
+        Self::from_unit_square(rectangle.tl_corner(), rectangle.tr_corner(), rectangle.br_corner())
+    }
+
+    pub fn normalising(rectangle: &Rectangle) -> Self {
+        Self::to_unit_square(rectangle.tl_corner(), rectangle.tr_corner(), rectangle.br_corner())
+    }
+
+    pub fn unnormalising_oriented(rectangle: &OrientedRectangle) -> Self {
+        Self::from_unit_square(rectangle.tl_corner(), rectangle.tr_corner(), rectangle.br_corner())
+    }
+
+    pub fn normalising_oriented(rectangle: &OrientedRectangle) -> Self {
+        Self::to_unit_square(rectangle.tl_corner(), rectangle.tr_corner(), rectangle.br_corner())
+    }
+
+    /// The bounding box of a rectangle after it has been transformed.
+    pub fn map_rectangle(&self, rectangle: &Rectangle) -> Rectangle {
+        let corners = self.apply(&[rectangle.tl_corner(), rectangle.tr_corner(), rectangle.bl_corner(), rectangle.br_corner()]);
+        Rectangle::from_points(&corners).unwrap()
+    }
+
+    pub fn to_f64(&self) -> PointTransformationAffine<f64> {
+        PointTransformationAffine::new(self.m.map(f64::from), self.b.map(f64::from))
+    }
+}
+
+impl PointTransformationAffine<f64> {
+    pub fn to_f32(&self) -> PointTransformationAffine {
+        PointTransformationAffine::new(self.m.map(|x| x as f32), self.b.map(|x| x as f32))
+    }
+}
+
+/// A projective transform (homography) of points, such as the mapping between two views of a flat surface.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointTransformationProjective<N: Real = f32> {
+    pub m: Matrix3<N>
+}
+
+impl<N: Real> From<PointTransformationAffine<N>> for PointTransformationProjective<N> {
+    fn from(affine: PointTransformationAffine<N>) -> Self {
+        Self::new(Matrix3::new(
+            affine.m[(0, 0)], affine.m[(0, 1)], affine.b.x,
+            affine.m[(1, 0)], affine.m[(1, 1)], affine.b.y,
+            N::zero(), N::zero(), N::one()
+        ))
+    }
+}
+
+impl<N: Real> Default for PointTransformationProjective<N> {
+    fn default() -> Self {
+        Self::new(Matrix3::identity())
+    }
+}
+
+impl<N: Real> PointTransformationProjective<N> {
+    pub fn new(m: Matrix3<N>) -> Self {
+        Self {
+            m
+        }
+    }
+
+    /// Transform a point. Points on the line that the transform sends to infinity have infinite coordinates.
+    pub fn mul(&self, p: Point<N>) -> Point<N> {
+        let q = self.m * nalgebra::Vector3::new(p.x, p.y, N::one());
+        Point::new(q.x / q.z, q.y / q.z)
+    }
+
+    /// Transform every point of a shape.
+    pub fn apply(&self, points: &[Point<N>]) -> Vec<Point<N>> {
+        points.iter().map(|point| self.mul(*point)).collect()
+    }
+
+    /// The transform that applies `other` first and then this transform.
+    pub fn compose(&self, other: &Self) -> Self {
+        Self::new(self.m * other.m)
+    }
+
+    /// The transform that undoes this transform. Fails if the transform is singular.
+    pub fn inverse(&self) -> Result<Self, Error> {
+        match self.m.try_inverse() {
+            Some(m) if m.iter().all(|value| is_finite(*value)) => Ok(Self::new(m)),
+            _ => Err(Error::DegenerateTransform("the transform is singular"))
         }
     }
 
-    mat
-}
\ No newline at end of file
+    /// Find the least squares projective transform from one set of at least 4 points to another,
+    /// with the normalised direct linear transform.
+    pub fn fit(from_points: &[Point<N>], to_points: &[Point<N>]) -> Result<Self, Error> {
+        check_lengths(from_points, to_points, 4)?;
+
+        if collinear(from_points) || collinear(to_points) {
+            return Err(Error::DegenerateTransform("the points are collinear"));
+        }
+
+        // Centering and scaling the points keeps the linear system well conditioned
+        let normalise_from = normalising(from_points);
+        let normalise_to = normalising(to_points);
+
+        let mut a = new_matrix(from_points.len() * 2, 8);
+        let mut b = new_matrix(from_points.len() * 2, 1);
+
+        for i in 0 .. from_points.len() {
+            let p = normalise_from.mul(from_points[i]);
+            let q = normalise_to.mul(to_points[i]);
+
+            let row = i * 2;
+            a[(row, 0)] = p.x;
+            a[(row, 1)] = p.y;
+            a[(row, 2)] = N::one();
+            a[(row, 6)] = -q.x * p.x;
+            a[(row, 7)] = -q.x * p.y;
+            b[(row, 0)] = q.x;
+
+            a[(row + 1, 3)] = p.x;
+            a[(row + 1, 4)] = p.y;
+            a[(row + 1, 5)] = N::one();
+            a[(row + 1, 6)] = -q.y * p.x;
+            a[(row + 1, 7)] = -q.y * p.y;
+            b[(row + 1, 0)] = q.y;
+        }
+
+        let h: DynamicMatrix<N> = a.pseudo_inverse(N::zero()) * b;
+        let normalised = Self::new(Matrix3::new(h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], N::one()));
+
+        let tform = Self::from(normalise_to).inverse()?.compose(&normalised).compose(&Self::from(normalise_from));
+
+        if tform.m.iter().all(|value| is_finite(*value)) && tform.m.determinant() != N::zero() {
+            Ok(tform)
+        } else {
+            Err(Error::DegenerateTransform("the points don't determine a unique transform"))
+        }
+    }
+}
+
+impl PointTransformationProjective {
+    pub fn to_f64(&self) -> PointTransformationProjective<f64> {
+        PointTransformationProjective::new(self.m.map(f64::from))
+    }
+}
+
+impl PointTransformationProjective<f64> {
+    pub fn to_f32(&self) -> PointTransformationProjective {
+        PointTransformationProjective::new(self.m.map(|x| x as f32))
+    }
+}
+
+// The similarity transform that moves the centroid of the points to the origin and their mean distance from it to sqrt(2).
+fn normalising<N: Real>(points: &[Point<N>]) -> PointTransformationAffine<N> {
+    let count: N = nalgebra::convert(points.len() as f64);
+    let mean = points.iter().fold(Point::zeros(), |sum, point| sum + point) / count;
+    let mean_distance = points.iter().fold(N::zero(), |sum, point| sum + (point - mean).norm()) / count;
+
+    let scale = N::sqrt(nalgebra::convert(2.0)) / mean_distance;
+    PointTransformationAffine::new(Matrix2::identity() * scale, -(mean * scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use transformations::*;
+    use Vector2;
+
+    fn close(a: &[Vector2], b: &[Vector2]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).norm() < 1e-3)
+    }
+
+    #[test]
+    fn affine() {
+        let points = [Vector2::new(0.0, 0.0), Vector2::new(4.0, 1.0), Vector2::new(2.0, 5.0), Vector2::new(-3.0, 2.0)];
+        let tform = PointTransformationAffine::new(Matrix2::new(1.5, -0.5, 0.25, 2.0), Vector2::new(3.0, -1.0));
+        let moved = tform.apply(&points);
+
+        let fitted = PointTransformationAffine::fit_affine(&points, &moved).unwrap();
+        assert!(close(&fitted.apply(&points), &moved));
+
+        let inverse = tform.inverse().unwrap();
+        assert!(close(&inverse.compose(&tform).apply(&points), &points));
+
+        let rotation = PointTransformationAffine::new(Matrix2::new(0.6, -0.8, 0.8, 0.6), Vector2::new(1.0, 2.0));
+        let rigid = PointTransformationAffine::fit_rigid(&points, &rotation.apply(&points)).unwrap();
+        assert!(close(&rigid.apply(&points), &rotation.apply(&points)));
+
+        let line = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0), Vector2::new(2.0, 2.0)];
+        assert!(PointTransformationAffine::fit_affine(&line, &line).is_err());
+        assert!(PointTransformationAffine::fit_similarity(&line[.. 1], &line[.. 1]).is_err());
+        assert!(PointTransformationAffine::new(Matrix2::zeros(), Vector2::zeros()).inverse().is_err());
+    }
+
+    #[test]
+    fn projective() {
+        let points = [
+            Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), Vector2::new(10.0, 10.0),
+            Vector2::new(0.0, 10.0), Vector2::new(5.0, 3.0)
+        ];
+
+        let tform = PointTransformationProjective::new(Matrix3::new(1.2, 0.1, 5.0, -0.2, 0.9, 3.0, 0.01, 0.002, 1.0));
+        let moved = tform.apply(&points);
+
+        let fitted = PointTransformationProjective::fit(&points, &moved).unwrap();
+        assert!(close(&fitted.apply(&points), &moved));
+        assert!(close(&fitted.inverse().unwrap().apply(&moved), &points));
+
+        assert!(PointTransformationProjective::fit(&points[.. 3], &moved[.. 3]).is_err());
+    }
+}
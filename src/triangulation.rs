@@ -0,0 +1,235 @@
+//! Delaunay triangulation of landmarks and piecewise affine warping between shapes, for face morphing,
+//! face swapping and texture mapping.
+
+use image::{GenericImage, ImageBuffer, Pixel};
+use {PointTransformationAffine, Vector2};
+use warp::{sample, Interpolation, Border};
+
+#[derive(Debug, Clone, Copy)]
+struct Triangle {
+    vertices: [usize; 3],
+    center: (f64, f64),
+    radius_squared: f64
+}
+
+impl Triangle {
+    fn new(points: &[Vector2], vertices: [usize; 3]) -> Option<Self> {
+        let [a, b, c] = vertices;
+        let (ax, ay) = (points[a].x as f64, points[a].y as f64);
+        let (bx, by) = (points[b].x as f64, points[b].y as f64);
+        let (cx, cy) = (points[c].x as f64, points[c].y as f64);
+
+        let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+
+        // Collinear points have no circumcircle
+        if d == 0.0 {
+            return None;
+        }
+
+        let a_squared = ax * ax + ay * ay;
+        let b_squared = bx * bx + by * by;
+        let c_squared = cx * cx + cy * cy;
+
+        let center = (
+            (a_squared * (by - cy) + b_squared * (cy - ay) + c_squared * (ay - by)) / d,
+            (a_squared * (cx - bx) + b_squared * (ax - cx) + c_squared * (bx - ax)) / d
+        );
+
+        Some(Self {
+            vertices, center,
+            radius_squared: (ax - center.0).powi(2) + (ay - center.1).powi(2)
+        })
+    }
+
+    fn circumcircle_contains(&self, point: Vector2) -> bool {
+        (point.x as f64 - self.center.0).powi(2) + (point.y as f64 - self.center.1).powi(2) < self.radius_squared
+    }
+}
+
+/// Find the Delaunay triangulation of a set of points, such as a shape from `ShapePredictor::run`,
+/// using the Bowyer-Watson algorithm. Each triangle is the indices of its 3 corners in `points`.
+///
+/// Points that duplicate an earlier point aren't part of any triangle.
+pub fn triangulate(points: &[Vector2]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+
+    let (mut min, mut max) = (points[0], points[0]);
+
+    for point in points {
+        min = Vector2::new(min.x.min(point.x), min.y.min(point.y));
+        max = Vector2::new(max.x.max(point.x), max.y.max(point.y));
+    }
+
+    // A triangle around every point, whose corners are added after the points
+    let center = (min + max) / 2.0;
+    let size = (max - min).norm().max(1.0) * 10.0;
+
+    let mut all_points = points.to_vec();
+    all_points.push(Vector2::new(center.x - size, center.y - size));
+    all_points.push(Vector2::new(center.x + size, center.y - size));
+    all_points.push(Vector2::new(center.x, center.y + size));
+
+    let n = points.len();
+    let mut triangles = vec![Triangle::new(&all_points, [n, n + 1, n + 2]).unwrap()];
+
+    for (idx, point) in points.iter().enumerate() {
+        if points[.. idx].contains(point) {
+            continue;
+        }
+
+        let (bad, good): (Vec<Triangle>, Vec<Triangle>) = triangles.into_iter()
+            .partition(|triangle| triangle.circumcircle_contains(*point));
+
+        // The boundary of the hole left by the bad triangles is made of the edges that only one of them has
+        let edges: Vec<(usize, usize)> = bad.iter()
+            .flat_map(|triangle| {
+                let [a, b, c] = triangle.vertices;
+                vec![(a, b), (b, c), (c, a)]
+            })
+            .collect();
+
+        triangles = good;
+
+        for &(a, b) in &edges {
+            let shared = edges.iter().filter(|&&(c, d)| (a, b) == (c, d) || (a, b) == (d, c)).count() > 1;
+
+            if !shared {
+                if let Some(triangle) = Triangle::new(&all_points, [a, b, idx]) {
+                    triangles.push(triangle);
+                }
+            }
+        }
+    }
+
+    triangles.into_iter()
+        .map(|triangle| triangle.vertices)
+        .filter(|vertices| vertices.iter().all(|&vertex| vertex < n))
+        .collect()
+}
+
+/// Add the corners and the middle of each edge of an image to a shape, so that a triangulation of it
+/// covers the whole image rather than only the convex hull of the landmarks.
+pub fn with_border_points(points: &[Vector2], width: u32, height: u32) -> Vec<Vector2> {
+    let (right, bottom) = (width.saturating_sub(1) as f32, height.saturating_sub(1) as f32);
+    let mut points = points.to_vec();
+
+    for &(x, y) in &[(0.0, 0.0), (0.5, 0.0), (1.0, 0.0), (1.0, 0.5), (1.0, 1.0), (0.5, 1.0), (0.0, 1.0), (0.0, 0.5)] {
+        points.push(Vector2::new(x * right, y * bottom));
+    }
+
+    points
+}
+
+/// Warp an image so that the points of `from_shape` end up at the points of `to_shape`, by warping each
+/// triangle of the triangulation with its own affine transform. Pixels of the output that aren't in
+/// any triangle are left black.
+pub fn warp_piecewise_affine<I>(image: &I, from_shape: &[Vector2], to_shape: &[Vector2], triangles: &[[usize; 3]],
+                                width: u32, height: u32) -> ImageBuffer<I::Pixel, Vec<<I::Pixel as Pixel>::Subpixel>>
+    where I: GenericImage, I::Pixel: 'static
+{
+    debug_assert_eq!(from_shape.len(), to_shape.len());
+
+    let mut output = ImageBuffer::new(width, height);
+
+    if image.width() == 0 || image.height() == 0 {
+        return output;
+    }
+
+    for &[a, b, c] in triangles {
+        let to = [to_shape[a], to_shape[b], to_shape[c]];
+        let from = [from_shape[a], from_shape[b], from_shape[c]];
+
+        // Map each output pixel back to the image, so that every output pixel gets a value
+        let tform = PointTransformationAffine::find_affine(&to, &from);
+
+        let area = cross(to[1] - to[0], to[2] - to[0]);
+        if area == 0.0 {
+            continue;
+        }
+
+        let min_x = to.iter().map(|p| p.x).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+        let min_y = to.iter().map(|p| p.y).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+        let max_x = to.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max).ceil().min(width as f32 - 1.0);
+        let max_y = to.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max).ceil().min(height as f32 - 1.0);
+
+        if max_x < 0.0 || max_y < 0.0 {
+            continue;
+        }
+
+        for y in min_y ..= max_y as u32 {
+            for x in min_x ..= max_x as u32 {
+                let point = Vector2::new(x as f32, y as f32);
+
+                // Barycentric coordinates, with a tolerance so that pixels on shared edges aren't missed
+                let u = cross(to[1] - point, to[2] - point) / area;
+                let v = cross(to[2] - point, to[0] - point) / area;
+                let w = 1.0 - u - v;
+
+                if u >= -1e-4 && v >= -1e-4 && w >= -1e-4 {
+                    output.put_pixel(x, y, sample(image, tform.mul(point), Interpolation::Bilinear, Border::Replicate));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn cross(a: Vector2, b: Vector2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+#[cfg(test)]
+mod tests {
+    use triangulation::*;
+    use image::{GrayImage, Luma};
+
+    #[test]
+    fn square_with_center() {
+        let points = [
+            Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), Vector2::new(10.0, 10.0),
+            Vector2::new(0.0, 10.0), Vector2::new(5.0, 5.0), Vector2::new(5.0, 5.0)
+        ];
+
+        let triangles = triangulate(&points);
+
+        assert_eq!(triangles.len(), 4);
+        assert!(triangles.iter().all(|triangle| triangle.contains(&4)));
+    }
+
+    #[test]
+    fn identity_warp() {
+        let image = GrayImage::from_fn(16, 12, |x, y| Luma([(x * 10 + y) as u8]));
+        let shape = with_border_points(&[Vector2::new(5.0, 4.0), Vector2::new(9.0, 7.0)], 16, 12);
+        let triangles = triangulate(&shape);
+
+        let warped = warp_piecewise_affine(&image, &shape, &shape, &triangles, 16, 12);
+
+        for (x, y, pixel) in warped.enumerate_pixels() {
+            assert!((pixel.data[0] as i32 - image.get_pixel(x, y).data[0] as i32).abs() <= 1, "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn shifted_warp() {
+        let image = GrayImage::from_fn(12, 8, |x, y| Luma([(x * 4 + y * 20) as u8]));
+        let from_shape = [
+            Vector2::new(2.0, 2.0), Vector2::new(9.0, 2.0), Vector2::new(9.0, 6.0),
+            Vector2::new(2.0, 6.0), Vector2::new(5.0, 4.0)
+        ];
+        let to_shape: Vec<Vector2> = from_shape.iter().map(|point| point + Vector2::new(0.25, 0.0)).collect();
+
+        let warped = warp_piecewise_affine(&image, &from_shape, &to_shape, &triangulate(&from_shape), 12, 8);
+
+        // Each pixel is sampled a quarter of a pixel to the left, and neighbouring pixels of the image differ by 4
+        for y in 3 .. 6 {
+            for x in 4 .. 9 {
+                assert_eq!(warped.get_pixel(x, y).data[0], (x * 4 + y * 20 - 1) as u8, "{} {}", x, y);
+            }
+        }
+
+        assert_eq!(warped.get_pixel(0, 0).data[0], 0);
+    }
+}
@@ -0,0 +1,323 @@
+use image::{Rgb, RgbImage};
+use line_drawing::Bresenham;
+use {CascadeSamples, Rectangle, Vector2};
+use std::fmt::Write;
+
+/// A group of landmarks that are connected by lines when drawn, such as an eye or the jaw line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub indices: Vec<usize>,
+    /// Whether the last landmark connects back to the first.
+    pub closed: bool
+}
+
+impl Region {
+    pub fn new(name: &str, indices: Vec<usize>, closed: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            indices, closed
+        }
+    }
+
+    /// The regions of the iBUG 300-W 68 point markup, used by dlib's face landmark model.
+    pub fn ibug_68() -> Vec<Region> {
+        vec![
+            Region::new("jaw", (0 .. 17).collect(), false),
+            Region::new("right eyebrow", (17 .. 22).collect(), false),
+            Region::new("left eyebrow", (22 .. 27).collect(), false),
+            Region::new("nose bridge", (27 .. 31).collect(), false),
+            Region::new("lower nose", (31 .. 36).collect(), false),
+            Region::new("nose tip", vec![30, 33], false),
+            Region::new("right eye", (36 .. 42).collect(), true),
+            Region::new("left eye", (42 .. 48).collect(), true),
+            Region::new("outer lips", (48 .. 60).collect(), true),
+            Region::new("inner lips", (60 .. 68).collect(), true)
+        ]
+    }
+
+    fn segments<'a>(&'a self, points: &'a [Vector2]) -> impl Iterator<Item=(Vector2, Vector2)> + 'a {
+        let closing = if self.closed && self.indices.len() > 2 {
+            Some((self.indices[self.indices.len() - 1], self.indices[0]))
+        } else {
+            None
+        };
+
+        self.indices.windows(2)
+            .map(|window| (window[0], window[1]))
+            .chain(closing)
+            .filter(move |&(a, b)| a < points.len() && b < points.len())
+            .map(move |(a, b)| (points[a], points[b]))
+    }
+}
+
+/// How landmarks are drawn.
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub point_color: Rgb<u8>,
+    pub line_color: Rgb<u8>,
+    pub rectangle_color: Rgb<u8>,
+    pub index_color: Rgb<u8>,
+    /// The radius of the square drawn for each landmark. A radius of 0 draws a single pixel.
+    pub point_radius: u32,
+    /// Whether to label each landmark with its index.
+    pub draw_indices: bool
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            point_color: Rgb([0, 255, 0]),
+            line_color: Rgb([255, 0, 0]),
+            rectangle_color: Rgb([0, 0, 255]),
+            index_color: Rgb([255, 255, 0]),
+            point_radius: 0,
+            draw_indices: false
+        }
+    }
+}
+
+fn put_pixel(image: &mut RgbImage, x: i32, y: i32, color: Rgb<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+// Clip a line to the image with the Liang-Barsky algorithm, so that lines to far away points
+// don't have to be walked pixel by pixel.
+fn clip_line(image: &RgbImage, start: Vector2, end: Vector2) -> Option<(Vector2, Vector2)> {
+    if !(start.x.is_finite() && start.y.is_finite() && end.x.is_finite() && end.y.is_finite()) {
+        return None;
+    }
+
+    let delta = end - start;
+    let max_x = image.width() as f32 - 1.0;
+    let max_y = image.height() as f32 - 1.0;
+    let mut t0: f32 = 0.0;
+    let mut t1: f32 = 1.0;
+
+    for &(p, q) in &[(-delta.x, start.x), (delta.x, max_x - start.x), (-delta.y, start.y), (delta.y, max_y - start.y)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+
+    if t0 > t1 {
+        None
+    } else {
+        Some((start + delta * t0, start + delta * t1))
+    }
+}
+
+/// Draw a line, skipping any part that lies outside the image.
+pub fn draw_line(image: &mut RgbImage, start: Vector2, end: Vector2, color: Rgb<u8>) {
+    if let Some((start, end)) = clip_line(image, start, end) {
+        for (x, y) in Bresenham::new((start.x as i32, start.y as i32), (end.x as i32, end.y as i32)) {
+            put_pixel(image, x, y, color);
+        }
+    }
+}
+
+// Whether a point is within `margin` pixels of the image. Points that aren't finite never are, and
+// points that are can be converted to pixel coordinates without overflowing.
+fn near_image(image: &RgbImage, point: &Vector2, margin: f32) -> bool {
+    point.x >= -margin && point.x <= image.width() as f32 + margin &&
+    point.y >= -margin && point.y <= image.height() as f32 + margin
+}
+
+/// Draw a square around each point, skipping any that lie outside the image.
+pub fn draw_points(image: &mut RgbImage, points: &[Vector2], radius: u32, color: Rgb<u8>) {
+    let margin = radius as f32;
+    let radius = radius as i32;
+
+    for point in points {
+        if !near_image(image, point, margin) {
+            continue;
+        }
+
+        for y in -radius ..= radius {
+            for x in -radius ..= radius {
+                put_pixel(image, point.x as i32 + x, point.y as i32 + y, color);
+            }
+        }
+    }
+}
+
+/// Draw the lines connecting the landmarks of each region.
+pub fn draw_regions(image: &mut RgbImage, points: &[Vector2], regions: &[Region], color: Rgb<u8>) {
+    for region in regions {
+        for (start, end) in region.segments(points) {
+            draw_line(image, start, end, color);
+        }
+    }
+}
+
+/// Draw the outline of a rectangle.
+pub fn draw_rectangle(image: &mut RgbImage, rectangle: &Rectangle, color: Rgb<u8>) {
+    let corners = [rectangle.tl_corner(), rectangle.tr_corner(), rectangle.br_corner(), rectangle.bl_corner()];
+
+    for i in 0 .. corners.len() {
+        draw_line(image, corners[i], corners[(i + 1) % corners.len()], color);
+    }
+}
+
+// A 3x5 pixel font for the digits 0-9, with each row stored in the lowest 3 bits
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111]
+];
+
+/// Label each point with its index, drawn just above and to the right of it.
+pub fn draw_indices(image: &mut RgbImage, points: &[Vector2], color: Rgb<u8>) {
+    for (i, point) in points.iter().enumerate() {
+        let label = i.to_string();
+
+        // The label is 4 pixels wide per digit, starting 2 pixels to the right and ending 6 pixels above
+        if !near_image(image, point, (label.len() * 4 + 6) as f32) {
+            continue;
+        }
+
+        let left = point.x as i32 + 2;
+        let top = point.y as i32 - 6;
+
+        for (n, digit) in label.bytes().enumerate() {
+            let glyph = &DIGITS[(digit - b'0') as usize];
+
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0 .. 3 {
+                    if row & (0b100 >> x) != 0 {
+                        put_pixel(image, left + n as i32 * 4 + x, top + y as i32, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draw a set of landmarks, the lines between them and optionally the rectangle they were found in.
+pub fn draw_landmarks(image: &mut RgbImage, points: &[Vector2], regions: &[Region], rectangle: Option<&Rectangle>, style: &Style) {
+    if let Some(rectangle) = rectangle {
+        draw_rectangle(image, rectangle, style.rectangle_color);
+    }
+
+    draw_regions(image, points, regions, style.line_color);
+    draw_points(image, points, style.point_radius, style.point_color);
+
+    if style.draw_indices {
+        draw_indices(image, points, style.index_color);
+    }
+}
+
+/// Draw the pixels sampled by one cascade, coloured from blue for dark pixels to red for bright ones,
+/// along with the landmarks after that cascade.
+pub fn draw_cascade_samples(image: &mut RgbImage, samples: &CascadeSamples, style: &Style) {
+    for (position, intensity) in samples.positions.iter().zip(&samples.intensities) {
+        let t = (intensity / 255.0).clamp(0.0, 1.0);
+        let color = Rgb([(t * 255.0) as u8, 0, ((1.0 - t) * 255.0) as u8]);
+        draw_points(image, &[*position], 0, color);
+    }
+
+    draw_points(image, &samples.shape, style.point_radius, style.point_color);
+}
+
+fn svg_color(color: Rgb<u8>) -> String {
+    format!("rgb({},{},{})", color.data[0], color.data[1], color.data[2])
+}
+
+// A region name as a class attribute value: spaces separate classes, and the rest must be escaped for XML.
+fn svg_class(name: &str) -> String {
+    name.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace(' ', "-")
+}
+
+/// Render a set of landmarks as an SVG overlay for an image of a given size.
+pub fn svg_overlay(width: u32, height: u32, points: &[Vector2], regions: &[Region], rectangle: Option<&Rectangle>, style: &Style) -> String {
+    // Writing to a string can't fail
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#, width, height).unwrap();
+
+    if let Some(rect) = rectangle {
+        writeln!(
+            svg, r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}"/>"#,
+            rect.x, rect.y, rect.width, rect.height, svg_color(style.rectangle_color)
+        ).unwrap();
+    }
+
+    for region in regions {
+        let coordinates: Vec<String> = region.indices.iter()
+            .filter(|&&i| i < points.len())
+            .map(|&i| format!("{},{}", points[i].x, points[i].y))
+            .collect();
+
+        writeln!(
+            svg, r#"  <{} class="{}" points="{}" fill="none" stroke="{}"/>"#,
+            if region.closed { "polygon" } else { "polyline" },
+            svg_class(&region.name), coordinates.join(" "), svg_color(style.line_color)
+        ).unwrap();
+    }
+
+    for point in points {
+        writeln!(
+            svg, r#"  <circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+            point.x, point.y, style.point_radius.max(1), svg_color(style.point_color)
+        ).unwrap();
+    }
+
+    if style.draw_indices {
+        for (i, point) in points.iter().enumerate() {
+            writeln!(
+                svg, r#"  <text x="{}" y="{}" font-size="8" fill="{}">{}</text>"#,
+                point.x + 2.0, point.y - 2.0, svg_color(style.index_color), i
+            ).unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use visualization::*;
+
+    #[test]
+    fn clipping() {
+        let mut image = RgbImage::new(10, 10);
+        let color = Rgb([255, 255, 255]);
+
+        // None of these should panic
+        draw_line(&mut image, Vector2::new(-100.0, 5.0), Vector2::new(100.0, 5.0), color);
+        draw_points(&mut image, &[Vector2::new(-1.0, -1.0), Vector2::new(10.0, 10.0), Vector2::new(f32::NAN, 0.0)], 2, color);
+        draw_points(&mut image, &[Vector2::new(3e9, 0.0), Vector2::new(0.0, -3e9)], 2, color);
+        draw_indices(&mut image, &[Vector2::new(9.0, 0.0), Vector2::new(3e9, 0.0), Vector2::new(0.0, -3e9)], color);
+
+        assert_eq!(image.get_pixel(0, 5), &color);
+        assert_eq!(image.get_pixel(9, 5), &color);
+        assert_eq!(image.get_pixel(9, 9), &color);
+    }
+
+    #[test]
+    fn svg_escaping() {
+        let points = [Vector2::new(1.0, 2.0), Vector2::new(3.0, 4.0)];
+        let regions = [Region::new(r#"left "eye" <&>"#, vec![0, 1], false)];
+
+        let svg = svg_overlay(10, 10, &points, &regions, None, &Style::default());
+        assert!(svg.contains(r#"class="left-&quot;eye&quot;-&lt;&amp;&gt;""#));
+    }
+}
@@ -0,0 +1,161 @@
+//! Resampling images through affine transforms, for extracting face chips, normalising rotation and augmenting data.
+
+use image::{GenericImage, ImageBuffer, Pixel};
+use num_traits::{Bounded, NumCast, ToPrimitive, Zero};
+use {PointTransformationAffine, Vector2};
+
+/// How pixel values between pixel centers are found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Use the closest pixel.
+    Nearest,
+    /// Blend the 4 closest pixels by distance.
+    Bilinear
+}
+
+/// What is sampled outside of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Border<P> {
+    /// A fixed pixel value, such as black.
+    Constant(P),
+    /// The closest pixel on the edge of the image.
+    Replicate,
+    /// The image mirrored at its edges, without repeating the edge pixels.
+    Reflect
+}
+
+// Mirror a coordinate into `0 .. size`, e.g. -1 becomes 1 and `size` becomes `size - 2`.
+fn reflect(coord: i64, size: i64) -> i64 {
+    if size == 1 {
+        return 0;
+    }
+
+    let period = 2 * (size - 1);
+    let coord = ((coord % period) + period) % period;
+
+    if coord < size {
+        coord
+    } else {
+        period - coord
+    }
+}
+
+fn pixel_at<I: GenericImage>(image: &I, x: i64, y: i64, border: Border<I::Pixel>) -> I::Pixel {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+
+    if x >= 0 && y >= 0 && x < width && y < height {
+        return image.get_pixel(x as u32, y as u32);
+    }
+
+    match border {
+        Border::Constant(pixel) => pixel,
+        Border::Replicate => image.get_pixel(x.max(0).min(width - 1) as u32, y.max(0).min(height - 1) as u32),
+        Border::Reflect => image.get_pixel(reflect(x, width) as u32, reflect(y, height) as u32)
+    }
+}
+
+/// Sample an image at a position between pixel centers. Pixel centers are at integer coordinates.
+pub fn sample<I: GenericImage>(image: &I, pos: Vector2, interpolation: Interpolation, border: Border<I::Pixel>) -> I::Pixel {
+    // An empty image has no edge to replicate or reflect, so it's all black
+    if image.width() == 0 || image.height() == 0 {
+        let zero = <<I::Pixel as Pixel>::Subpixel as Zero>::zero();
+
+        return match border {
+            Border::Constant(pixel) => pixel,
+            _ => I::Pixel::from_channels(zero, zero, zero, zero)
+        };
+    }
+
+    if interpolation == Interpolation::Nearest || !pos.x.is_finite() || !pos.y.is_finite() {
+        return pixel_at(image, pos.x.round() as i64, pos.y.round() as i64, border);
+    }
+
+    let (x0, y0) = (pos.x.floor(), pos.y.floor());
+    let (fx, fy) = (pos.x - x0, pos.y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let corners = [
+        (pixel_at(image, x0, y0, border), (1.0 - fx) * (1.0 - fy)),
+        (pixel_at(image, x0 + 1, y0, border), fx * (1.0 - fy)),
+        (pixel_at(image, x0, y0 + 1, border), (1.0 - fx) * fy),
+        (pixel_at(image, x0 + 1, y0 + 1, border), fx * fy)
+    ];
+
+    let mut pixel = corners[0].0;
+    let max = <<I::Pixel as Pixel>::Subpixel as Bounded>::max_value().to_f32().unwrap_or(1.0);
+
+    // Integer subpixels are rounded, as casting would truncate them
+    let half: Option<<I::Pixel as Pixel>::Subpixel> = NumCast::from(0.5);
+    let integer = half.and_then(|half| half.to_f32()) != Some(0.5);
+
+    for (channel, value) in pixel.channels_mut().iter_mut().enumerate() {
+        let sum: f32 = corners.iter()
+            .map(|&(ref corner, weight)| corner.channels()[channel].to_f32().unwrap_or(0.0) * weight)
+            .sum();
+
+        let sum = if integer { sum.round() } else { sum };
+        *value = NumCast::from(sum.max(0.0).min(max)).unwrap_or(*value);
+    }
+
+    pixel
+}
+
+/// Resample an image into a new image of `width` by `height` pixels. `tform` maps each pixel of the
+/// output to the position in `image` that it is sampled from, like dlib's `transform_image`. Use
+/// `PointTransformationAffine::inverse` to warp with a transform that maps the image to the output.
+pub fn warp_affine<I>(image: &I, tform: &PointTransformationAffine, width: u32, height: u32,
+                      interpolation: Interpolation, border: Border<I::Pixel>) -> ImageBuffer<I::Pixel, Vec<<I::Pixel as Pixel>::Subpixel>>
+    where I: GenericImage, I::Pixel: 'static
+{
+    ImageBuffer::from_fn(width, height, |x, y| {
+        sample(image, tform.mul(Vector2::new(x as f32, y as f32)), interpolation, border)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::*;
+    use image::{GrayImage, Luma, RgbImage, Rgb};
+    use nalgebra::Matrix2;
+
+    #[test]
+    fn reflection() {
+        let reflected: Vec<i64> = (-3 .. 7).map(|coord| reflect(coord, 4)).collect();
+        assert_eq!(reflected, [3, 2, 1, 0, 1, 2, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn translation() {
+        let image = GrayImage::from_fn(8, 6, |x, y| Luma([(x * 20 + y) as u8]));
+        let tform = PointTransformationAffine::new(Matrix2::identity(), Vector2::new(2.0, 1.0));
+
+        let warped = warp_affine(&image, &tform, 8, 6, Interpolation::Bilinear, Border::Constant(Luma([255])));
+
+        assert_eq!(warped.get_pixel(0, 0), image.get_pixel(2, 1));
+        assert_eq!(warped.get_pixel(5, 4), image.get_pixel(7, 5));
+        assert_eq!(warped.get_pixel(6, 0).data[0], 255);
+
+        let replicated = warp_affine(&image, &tform, 8, 6, Interpolation::Nearest, Border::Replicate);
+        assert_eq!(replicated.get_pixel(7, 5), image.get_pixel(7, 5));
+    }
+
+    #[test]
+    fn bilinear_rgb() {
+        let image = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 100, 50, 200 - x as u8 * 100]));
+        let pixel = sample(&image, Vector2::new(0.5, 0.0), Interpolation::Bilinear, Border::Replicate);
+
+        assert_eq!(pixel, Rgb([50, 50, 150]));
+
+        let rounded = sample(&GrayImage::from_fn(2, 1, |x, _| Luma([x as u8 * 3])), Vector2::new(0.5, 0.0), Interpolation::Bilinear, Border::Replicate);
+        assert_eq!(rounded, Luma([2]));
+    }
+
+    #[test]
+    fn empty_image() {
+        let image = GrayImage::new(0, 0);
+
+        assert_eq!(sample(&image, Vector2::new(1.0, 2.0), Interpolation::Bilinear, Border::Replicate), Luma([0]));
+        assert_eq!(sample(&image, Vector2::new(1.0, 2.0), Interpolation::Nearest, Border::Reflect), Luma([0]));
+        assert_eq!(sample(&image, Vector2::new(1.0, 2.0), Interpolation::Nearest, Border::Constant(Luma([7]))), Luma([7]));
+    }
+}